@@ -1,5 +1,6 @@
 use gristmill::{
     asset,
+    color::LinSrgba,
     input::{InputEvent, InputSystem},
     two::QuadRenderer,
     Event, Game, GameLoader, Obj, RenderingContext,
@@ -28,6 +29,7 @@ struct ButtonGame {
 impl ButtonGame {
     fn new(input_system: InputSystem, renderer: QuadRenderer) -> Self {
         let mut gui = Gui::new();
+        gui.set_background(Some(LinSrgba::new(0.05, 0.05, 0.08, 1.)));
         let root = gui.root();
         gui.set_style(
             root,
@@ -46,12 +48,13 @@ impl ButtonGame {
         };
 
         let label = Obj::new(Label::new());
-        let label1 = label.clone();
-        times_clicked.changed.add_listener(move |value| {
-            label1
-                .get_mut()
-                .set_text(format!("Times Clicked: {}", *value));
-        });
+        times_clicked
+            .changed
+            .add_weak_listener(label.downgrade(), |label, value| {
+                label
+                    .get_mut()
+                    .set_text(format!("Times Clicked: {}", *value));
+            });
 
         let mut button = Button::with_label("Click Me!", SimpleButtonView::default());
         button.add_pressed_listener(move |&()| {
@@ -67,14 +70,8 @@ impl ButtonGame {
             },
         );
 
-        gui.add_view(
-            root,
-            label,
-            Style {
-                size: Size::from_lengths(256., 32.),
-                ..Default::default()
-            },
-        );
+        gui.add_view(root, label, Style::DEFAULT);
+        gui.set_text_measurer(renderer.text_measurer());
 
         ButtonGame {
             input_system,
@@ -90,6 +87,11 @@ impl Game for ButtonGame {
         self.gui.set_screen_size(width, height);
     }
 
+    fn dpi_scale_changed(&mut self, dpi_scale: f32) {
+        self.renderer.set_dpi_scale(dpi_scale);
+        self.gui.set_dpi_scale(dpi_scale);
+    }
+
     fn handle_event(&mut self, event: InputEvent) {
         self.input_system.handle_event(event);
     }
@@ -99,6 +101,8 @@ impl Game for ButtonGame {
         self.gui
             .handle_pointer_motion(pointer.position.x, pointer.position.y);
         self.gui.handle_pointer_button(pointer.primary);
+        self.gui.layout();
+        gristmill::window::set_cursor_icon(self.gui.cursor_icon());
 
         if self.input_system.get("exit").pressed() {
             gristmill::window::request_quit();
@@ -19,12 +19,12 @@ impl Player {
 }
 
 impl Renderable for Player {
-    fn render(&self, renderer: &mut QuadRenderer) {
+    fn quad(&self) -> Option<(Option<&Texture>, Rect)> {
         let rect = Rect {
             position: self.position,
             size: Self::SIZE,
         };
-        renderer.queue_texture(rect, &self.texture);
+        Some((Some(&self.texture), rect))
     }
 }
 
@@ -94,6 +94,10 @@ impl GameLoader for WasdGame {
         InputSystem::create_default_config_if_missing()
     }
 
+    fn queue_assets(queue: &mut asset::LoadQueue) {
+        queue.image("images", "player.png");
+    }
+
     fn load(context: &mut RenderingContext) -> asset::Result<Self::Assets> {
         let input_system = InputSystem::load_config()?;
         let player_texture = asset::load_png_file(context, "images", "player.png")?;
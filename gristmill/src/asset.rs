@@ -3,6 +3,12 @@ use std::{
     fs::File,
     io::{Error as IoError, Read, Write},
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::SystemTime,
 };
 
 use crate::{Font, RenderingContext, Texture};
@@ -20,11 +26,20 @@ pub fn base_path() -> PathBuf {
 #[cfg(not(debug_assertions))]
 pub fn base_path() -> PathBuf {
     // TODO cache this
-    let mut dir = env::current_exe().unwrap();
+    let mut dir = std::env::current_exe().unwrap();
     dir.pop();
     dir
 }
 
+// What went wrong, without the details. Io covers missing files (see AssetError::not_found) and permission problems,
+// and Format covers files that were read but couldn't be parsed or decoded.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AssetErrorKind {
+    Io,
+    Format,
+    Other,
+}
+
 #[derive(Debug)]
 enum ErrorKind {
     IoError(IoError),
@@ -40,7 +55,7 @@ pub struct AssetError {
 }
 
 impl AssetError {
-    fn new_io(path: PathBuf, write: bool, error: IoError) -> Self {
+    pub(crate) fn new_io(path: PathBuf, write: bool, error: IoError) -> Self {
         AssetError {
             path,
             write,
@@ -56,6 +71,22 @@ impl AssetError {
             info: error.to_string(),
         }
     }
+    fn new_json(path: PathBuf, write: bool, error: serde_json::Error) -> Self {
+        AssetError {
+            path,
+            write,
+            kind: ErrorKind::Format,
+            info: error.to_string(),
+        }
+    }
+    pub(crate) fn new_format(path: PathBuf, write: bool, info: String) -> Self {
+        AssetError {
+            path,
+            write,
+            kind: ErrorKind::Format,
+            info,
+        }
+    }
     fn new_png(path: PathBuf, error: png::DecodingError) -> Self {
         match error {
             png::DecodingError::IoError(error) => AssetError {
@@ -79,6 +110,26 @@ impl AssetError {
         }
     }
 
+    pub fn kind(&self) -> AssetErrorKind {
+        match self.kind {
+            ErrorKind::IoError(_) => AssetErrorKind::Io,
+            ErrorKind::Format => AssetErrorKind::Format,
+            ErrorKind::Other => AssetErrorKind::Other,
+        }
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    // Whether the error happened while saving rather than loading.
+    pub fn is_write(&self) -> bool {
+        self.write
+    }
+    pub fn io_error(&self) -> Option<&IoError> {
+        match &self.kind {
+            ErrorKind::IoError(error) => Some(error),
+            _ => None,
+        }
+    }
     pub fn not_found(&self) -> bool {
         if let ErrorKind::IoError(error) = &self.kind {
             error.kind() == std::io::ErrorKind::NotFound
@@ -114,7 +165,12 @@ impl std::fmt::Display for AssetError {
     }
 }
 
-impl std::error::Error for AssetError {}
+impl std::error::Error for AssetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.io_error()
+            .map(|error| error as &(dyn std::error::Error + 'static))
+    }
+}
 
 pub type Result<T> = std::result::Result<T, AssetError>;
 
@@ -134,32 +190,160 @@ pub fn open_writer(path: &Path) -> Result<BufWriter> {
     let file = File::create(path).map_err(|e| AssetError::new_io(path.to_owned(), true, e))?;
     Ok(BufWriter::new(file))
 }
+// Writes to a temporary file next to the target, then renames it over the target. A crash or full disk partway
+// through leaves the old file as it was, rather than a truncated one.
+pub fn write_file(path: &Path, bytes: &[u8]) -> Result<()> {
+    println!("Writing {}", path.to_string_lossy());
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+    let result = File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(bytes)?;
+            file.sync_all()
+        })
+        .and_then(|()| std::fs::rename(&temp_path, path));
+    if let Err(error) = result {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(AssetError::new_io(path.to_owned(), true, error));
+    }
+    Ok(())
+}
+
+// Reports when a file changes on disk, so it can be reloaded while the game runs. Only debug builds load assets from
+// the project, so in release builds this never reports a change. A file that's deleted isn't reported until it's
+// written again, so an editor that saves by replacing the file causes a single reload.
+pub struct AssetWatch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl AssetWatch {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    pub fn changed(&mut self) -> bool {
+        if !cfg!(debug_assertions) {
+            return false;
+        }
+        match std::fs::metadata(&self.path).and_then(|metadata| metadata.modified()) {
+            Ok(modified) if Some(modified) != self.modified => {
+                self.modified = Some(modified);
+                true
+            }
+            _ => false,
+        }
+    }
+}
 
-pub fn create_dir(dir: &str) {
+pub fn watch(prefix: &str, file: &str) -> AssetWatch {
+    let path = get_path(prefix, file);
+    let modified = std::fs::metadata(&path)
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    AssetWatch { path, modified }
+}
+
+// Whether the file is on disk or in the bundle.
+pub fn exists(prefix: &str, file: &str) -> bool {
+    path_exists(&get_path(prefix, file))
+}
+fn path_exists(path: &Path) -> bool {
+    path.exists() || bundled_file(path).is_some()
+}
+
+// Files built into the executable, so a game can be distributed as a single file. Set it with set_bundle before
+// run_game. Loading a file that isn't on disk falls back to the bundle, so files the game writes (like the config
+// files) and loose files placed next to the executable take priority. Saving always writes to disk.
+#[derive(Default)]
+pub struct Bundle {
+    files: Vec<(PathBuf, &'static [u8])>,
+}
+
+impl Bundle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // The path is relative to the assets, like "images/player.png". Usually the bytes come from include_bytes!.
+    pub fn add(&mut self, path: &str, bytes: &'static [u8]) {
+        self.files.push((PathBuf::from(path), bytes));
+    }
+    // Adds every file in a tar archive (usually from include_bytes!), with paths relative to the archive's root.
+    // Only plain files are used, so directories and links are skipped.
+    pub fn add_tar(&mut self, tar: &'static [u8]) -> std::result::Result<(), String> {
+        let field = |header: &'static [u8], start: usize, end: usize| {
+            let field = &header[start..end];
+            let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+            std::str::from_utf8(&field[..len]).map_err(|_| "Invalid tar header".to_string())
+        };
+        let mut offset = 0;
+        while let Some(header) = tar.get(offset..offset + 512) {
+            // The archive ends with empty blocks.
+            if header.iter().all(|&b| b == 0) {
+                break;
+            }
+            let size_field = field(header, 124, 136)?.trim();
+            let size = usize::from_str_radix(size_field, 8)
+                .map_err(|_| format!("Invalid tar file size \"{}\"", size_field))?;
+            let start = offset + 512;
+            let bytes = tar
+                .get(start..start + size)
+                .ok_or("Tar archive is truncated")?;
+            if matches!(header[156], b'0' | 0) {
+                let (name, prefix) = (field(header, 0, 100)?, field(header, 345, 500)?);
+                let mut path = PathBuf::from(prefix);
+                path.push(name.trim_start_matches("./"));
+                self.files.push((path, bytes));
+            }
+            offset = start + size.div_ceil(512) * 512;
+        }
+        Ok(())
+    }
+}
+
+static BUNDLE: Mutex<Option<Bundle>> = Mutex::new(None);
+
+pub fn set_bundle(bundle: Bundle) {
+    *BUNDLE.lock().unwrap() = Some(bundle);
+}
+
+// Only used when the file isn't on disk.
+fn bundled_file(path: &Path) -> Option<&'static [u8]> {
+    let bundle = BUNDLE.lock().unwrap();
+    let files = &bundle.as_ref()?.files;
+    if path.exists() {
+        return None;
+    }
+    let base_path = base_path();
+    let relative_path = path.strip_prefix(&base_path).unwrap_or(path);
+    files
+        .iter()
+        .find(|(bundle_path, _)| bundle_path == relative_path)
+        .map(|(_, bytes)| *bytes)
+}
+
+pub fn create_dir(dir: &str) -> Result<()> {
     let mut dir_path = base_path();
     dir_path.push(dir);
     if !dir_path.exists() {
         println!("Creating directory {}", dir_path.to_string_lossy());
-        std::fs::create_dir(dir_path).expect("could not create directory");
+        std::fs::create_dir(&dir_path).map_err(|e| AssetError::new_io(dir_path, true, e))?;
     }
+    Ok(())
 }
 
 pub fn load_text_file(prefix: &str, file: &str) -> Result<String> {
     let path = get_path(prefix, file);
-    let mut reader = open_reader(&path)?;
-    let mut string = String::new();
-    reader
-        .read_to_string(&mut string)
-        .map_err(|e| AssetError::new_io(path, false, e))?;
-    Ok(string)
+    String::from_utf8(load_bytes(&path)?).map_err(|e| {
+        AssetError::new_io(
+            path,
+            false,
+            IoError::new(std::io::ErrorKind::InvalidData, e),
+        )
+    })
 }
 pub fn save_text_file(prefix: &str, file: &str, value: &str) -> Result<()> {
-    let path = get_path(prefix, file);
-    let mut writer = open_writer(&path)?;
-    writer
-        .write_all(value.as_bytes())
-        .map_err(|e| AssetError::new_io(path, true, e))?;
-    Ok(())
+    write_file(&get_path(prefix, file), value.as_bytes())
 }
 
 pub fn load_yaml_file<T>(prefix: &str, file: &str) -> Result<T>
@@ -167,17 +351,15 @@ where
     T: DeserializeOwned,
 {
     let path = get_path(prefix, file);
-    let reader = open_reader(&path)?;
-    serde_yml::from_reader(reader).map_err(|e| AssetError::new_yaml(path, false, e))
+    serde_yml::from_slice(&load_bytes(&path)?).map_err(|e| AssetError::new_yaml(path, false, e))
 }
 pub fn load_yaml_file_or_default<T>(prefix: &str, file: &str) -> Result<T>
 where
     T: DeserializeOwned + Default,
 {
     let path = get_path(prefix, file);
-    if path.exists() {
-        let reader = open_reader(&path)?;
-        serde_yml::from_reader(reader).map_err(|e| AssetError::new_yaml(path, false, e))
+    if path_exists(&path) {
+        serde_yml::from_slice(&load_bytes(&path)?).map_err(|e| AssetError::new_yaml(path, false, e))
     } else {
         println!(
             "{} does not exist, using defaults instead",
@@ -190,42 +372,569 @@ pub fn save_yaml_file<T>(prefix: &str, file: &str, value: &T) -> Result<()>
 where
     T: Serialize,
 {
+    // Serialized first, so an error doesn't touch the file.
     let path = get_path(prefix, file);
-    let writer = open_writer(&path)?;
-    serde_yml::to_writer(writer, value).map_err(|e| AssetError::new_yaml(path, true, e))
+    let yaml =
+        serde_yml::to_string(value).map_err(|e| AssetError::new_yaml(path.clone(), true, e))?;
+    write_file(&path, yaml.as_bytes())
 }
 
-pub fn load_png_file(context: &mut RenderingContext, prefix: &str, file: &str) -> Result<Texture> {
+pub fn load_json_file<T>(prefix: &str, file: &str) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let path = get_path(prefix, file);
+    serde_json::from_slice(&load_bytes(&path)?).map_err(|e| AssetError::new_json(path, false, e))
+}
+pub fn load_json_file_or_default<T>(prefix: &str, file: &str) -> Result<T>
+where
+    T: DeserializeOwned + Default,
+{
+    let path = get_path(prefix, file);
+    if path_exists(&path) {
+        serde_json::from_slice(&load_bytes(&path)?)
+            .map_err(|e| AssetError::new_json(path, false, e))
+    } else {
+        println!(
+            "{} does not exist, using defaults instead",
+            path.to_string_lossy()
+        );
+        Ok(Default::default())
+    }
+}
+pub fn save_json_file<T>(prefix: &str, file: &str, value: &T) -> Result<()>
+where
+    T: Serialize,
+{
+    let path = get_path(prefix, file);
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| AssetError::new_json(path.clone(), true, e))?;
+    write_file(&path, json.as_bytes())
+}
+
+// Adds keys from `from` that are missing in `into`, recursing into nested mappings. Existing values are kept.
+fn merge_missing_keys(into: &mut serde_yml::Value, from: serde_yml::Value) -> bool {
+    let (into, from) = match (into, from) {
+        (serde_yml::Value::Mapping(into), serde_yml::Value::Mapping(from)) => (into, from),
+        _ => return false,
+    };
+    let mut changed = false;
+    for (key, value) in from {
+        match into.get_mut(&key) {
+            Some(existing) => changed |= merge_missing_keys(existing, value),
+            None => {
+                into.insert(key, value);
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+// When fields are added to a config type, existing files are missing them and get the serde defaults on load.
+// This writes those defaults back into the file so users can see and edit them. Values already in the file (and keys
+// the type doesn't know about) are left alone. Returns whether the file was changed.
+pub fn upgrade_yaml_file<T>(prefix: &str, file: &str) -> Result<bool>
+where
+    T: Serialize + DeserializeOwned,
+{
     let path = get_path(prefix, file);
     let reader = open_reader(&path)?;
-    let decoder = png::Decoder::new(reader);
-    let mut image_reader = decoder
-        .read_info()
-        .map_err(|e| AssetError::new_png(path.clone(), e))?;
+    let mut file_value: serde_yml::Value =
+        serde_yml::from_reader(reader).map_err(|e| AssetError::new_yaml(path.clone(), false, e))?;
+    let value: T = serde_yml::from_value(file_value.clone())
+        .map_err(|e| AssetError::new_yaml(path.clone(), false, e))?;
+    let full_value =
+        serde_yml::to_value(&value).map_err(|e| AssetError::new_yaml(path.clone(), true, e))?;
+    if !merge_missing_keys(&mut file_value, full_value) {
+        return Ok(false);
+    }
+    let yaml = serde_yml::to_string(&file_value)
+        .map_err(|e| AssetError::new_yaml(path.clone(), true, e))?;
+    write_file(&path, yaml.as_bytes())?;
+    Ok(true)
+}
+
+static INVALID_TEXTURE_FALLBACK: AtomicBool = AtomicBool::new(false);
+
+// When enabled, load_image_file prints the error and returns the (magenta) invalid texture, so a missing image shows
+// up in game instead of stopping it.
+pub fn set_invalid_texture_fallback(enabled: bool) {
+    INVALID_TEXTURE_FALLBACK.store(enabled, Ordering::Relaxed);
+}
+
+pub fn load_png_file(context: &mut RenderingContext, prefix: &str, file: &str) -> Result<Texture> {
+    load_image_file(context, prefix, file)
+}
+
+// Loads a PNG, BMP or JPEG image, picking the decoder from the file extension. With the invalid texture fallback enabled
+// (see set_invalid_texture_fallback), errors are printed and the invalid texture is returned instead.
+pub fn load_image_file(
+    context: &mut RenderingContext,
+    prefix: &str,
+    file: &str,
+) -> Result<Texture> {
+    match read_image_file(context, prefix, file) {
+        Err(error) if INVALID_TEXTURE_FALLBACK.load(Ordering::Relaxed) => {
+            eprintln!("{}", error);
+            Ok(Texture::invalid(context))
+        }
+        result => result,
+    }
+}
+
+fn read_image_file(context: &mut RenderingContext, prefix: &str, file: &str) -> Result<Texture> {
+    let path = get_path(prefix, file);
+    let image = match take_preloaded(&path) {
+        Some(Ok(Preloaded::Image(image))) => image,
+        Some(Err(error)) => return Err(error),
+        _ => decode_image_file(path)?,
+    };
+    Ok(image.into_texture(context))
+}
+
+fn decode_image_file(path: PathBuf) -> Result<Image> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let image = match extension.as_str() {
+        "png" => {
+            let bytes = read_bytes(&path)?;
+            decode_png(bytes.as_slice()).map_err(|e| AssetError::new_png(path.clone(), e))?
+        }
+        "bmp" => {
+            let bytes = read_bytes(&path)?;
+            decode_bmp(&bytes).map_err(|info| AssetError::new_format(path.clone(), false, info))?
+        }
+        "jpg" | "jpeg" => {
+            let bytes = read_bytes(&path)?;
+            decode_jpeg(&bytes).map_err(|info| AssetError::new_format(path.clone(), false, info))?
+        }
+        _ => {
+            return Err(AssetError::new_format(
+                path,
+                false,
+                format!("Unknown image format \"{}\"", extension),
+            ))
+        }
+    };
+    Ok(image)
+}
+
+pub(crate) struct Image {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+impl Image {
+    pub(crate) fn into_texture(self, context: &mut RenderingContext) -> Texture {
+        Texture::new_rgba8(
+            context,
+            self.width.try_into().unwrap(),
+            self.height.try_into().unwrap(),
+            &self.rgba,
+        )
+    }
+}
+
+// Decodes a PNG, BMP or JPEG image in memory, detected from its header. Errors are a description of the problem.
+pub(crate) fn decode_image(bytes: &[u8]) -> std::result::Result<Image, String> {
+    if bytes.starts_with(&[0x89, b'P', b'N', b'G']) {
+        decode_png(bytes).map_err(|e| e.to_string())
+    } else if bytes.starts_with(b"BM") {
+        decode_bmp(bytes)
+    } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+        decode_jpeg(bytes)
+    } else {
+        Err("Unknown image format (only PNG, BMP and JPEG are supported)".to_string())
+    }
+}
+
+fn decode_png<R: Read>(reader: R) -> std::result::Result<Image, png::DecodingError> {
+    let mut decoder = png::Decoder::new(reader);
+    // Palettes and bit depths other than 8 are converted, so only the color type is left to handle.
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut image_reader = decoder.read_info()?;
     let mut buffer = vec![0; image_reader.output_buffer_size()];
-    let info = image_reader
-        .next_frame(&mut buffer)
-        .map_err(|e| AssetError::new_png(path, e))?;
+    let info = image_reader.next_frame(&mut buffer)?;
     buffer.truncate(info.buffer_size());
-    Ok(Texture::new_rgba8(
-        context,
-        info.width.try_into().unwrap(),
-        info.height.try_into().unwrap(),
-        &buffer,
-    ))
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        png::ColorType::Indexed => unreachable!("palette is expanded"),
+    };
+    Ok(Image {
+        width: info.width,
+        height: info.height,
+        rgba,
+    })
+}
+
+// Uncompressed 24 and 32 bit BMP files, which is what image editors write by default.
+fn decode_bmp(bytes: &[u8]) -> std::result::Result<Image, String> {
+    let u16_at = |offset: usize| {
+        bytes
+            .get(offset..offset + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .ok_or("BMP file is truncated")
+    };
+    let u32_at = |offset: usize| {
+        bytes
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or("BMP file is truncated")
+    };
+    let data_offset = u32_at(10)? as usize;
+    let width = u32_at(18)? as i32;
+    let height = u32_at(22)? as i32;
+    let bits = u16_at(28)?;
+    let compression = u32_at(30)?;
+    // Without bitfields, 32 bit pixels are BGR with an unused byte.
+    let (masks, has_alpha) = match (bits, compression) {
+        (24, 0) => ([0xff0000, 0xff00, 0xff, 0], false),
+        (32, 0) => ([0xff0000, 0xff00, 0xff, 0], false),
+        (32, 3) => {
+            let alpha = if u32_at(14)? >= 56 { u32_at(66)? } else { 0 };
+            ([u32_at(54)?, u32_at(58)?, u32_at(62)?, alpha], alpha != 0)
+        }
+        _ => {
+            return Err(format!(
+                "Unsupported BMP format ({} bit, compression {})",
+                bits, compression
+            ))
+        }
+    };
+    if masks[..3].contains(&0) {
+        return Err("Unsupported BMP format (a color channel has no bits)".to_string());
+    }
+    if masks
+        .iter()
+        .any(|&mask| mask != 0 && mask.count_ones() != 8)
+    {
+        return Err("Unsupported BMP format (channels aren't 8 bit)".to_string());
+    }
+    if width <= 0 || height == 0 {
+        return Err("BMP file has no pixels".to_string());
+    }
+    let (width, rows) = (width as usize, height.unsigned_abs() as usize);
+    let pixel_size = bits as usize / 8;
+    // The header can't be trusted, so check the pixels fit in the file before allocating for them. The last row
+    // doesn't need its padding.
+    let row_size = width
+        .checked_mul(pixel_size)
+        .ok_or("BMP file is truncated")?;
+    let stride = row_size.div_ceil(4) * 4;
+    let end = stride
+        .checked_mul(rows - 1)
+        .and_then(|size| size.checked_add(row_size))
+        .and_then(|size| size.checked_add(data_offset))
+        .ok_or("BMP file is truncated")?;
+    if end > bytes.len() {
+        return Err("BMP file is truncated".to_string());
+    }
+    let mut rgba = Vec::with_capacity(width * rows * 4);
+    for row in 0..rows {
+        // Rows are stored bottom to top, unless the height is negative.
+        let source_row = if height > 0 { rows - 1 - row } else { row };
+        let start = data_offset + source_row * stride;
+        let row_bytes = &bytes[start..start + row_size];
+        for pixel in row_bytes.chunks_exact(pixel_size) {
+            let mut value = [0; 4];
+            value[..pixel_size].copy_from_slice(pixel);
+            let value = u32::from_le_bytes(value);
+            let channel = |mask: u32| ((value & mask) >> mask.trailing_zeros()) as u8;
+            let alpha = if has_alpha { channel(masks[3]) } else { 255 };
+            rgba.extend_from_slice(&[
+                channel(masks[0]),
+                channel(masks[1]),
+                channel(masks[2]),
+                alpha,
+            ]);
+        }
+    }
+    Ok(Image {
+        width: width as u32,
+        height: rows as u32,
+        rgba,
+    })
+}
+
+// Grayscale and RGB JPEG files. CMYK ones (from print workflows) aren't supported.
+fn decode_jpeg(bytes: &[u8]) -> std::result::Result<Image, String> {
+    let mut decoder = jpeg_decoder::Decoder::new(bytes);
+    let pixels = decoder.decode().map_err(|e| e.to_string())?;
+    let info = decoder.info().ok_or("JPEG file has no image")?;
+    let rgba = match info.pixel_format {
+        jpeg_decoder::PixelFormat::RGB24 => pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        jpeg_decoder::PixelFormat::L8 => pixels.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        jpeg_decoder::PixelFormat::L16 => pixels
+            .chunks_exact(2)
+            .flat_map(|p| {
+                let v = (u16::from_ne_bytes([p[0], p[1]]) >> 8) as u8;
+                [v, v, v, 255]
+            })
+            .collect(),
+        jpeg_decoder::PixelFormat::CMYK32 => {
+            return Err("Unsupported JPEG format (CMYK)".to_string())
+        }
+    };
+    Ok(Image {
+        width: info.width.into(),
+        height: info.height.into(),
+        rgba,
+    })
 }
 
 pub fn load_font_file(prefix: &str, file: &str) -> Result<Font> {
     let path = get_path(prefix, file);
-    let mut reader = open_reader(&path)?;
-    let mut buf = Vec::new();
-    reader
-        .read_to_end(&mut buf)
-        .map_err(|e| AssetError::new_io(path.clone(), false, e))?;
-    Font::try_from_vec(buf).map_err(|_e| AssetError {
+    Font::try_from_vec(load_bytes(&path)?).map_err(|_e| AssetError {
         path,
         write: false,
         kind: ErrorKind::Format,
         info: "Invalid font".to_string(),
     })
 }
+
+// A file queued with a LoadQueue, or else read now.
+fn load_bytes(path: &Path) -> Result<Vec<u8>> {
+    match take_preloaded_bytes(path) {
+        Some(bytes) => bytes,
+        None => read_bytes(path),
+    }
+}
+fn read_bytes(path: &Path) -> Result<Vec<u8>> {
+    if let Some(bytes) = bundled_file(path) {
+        println!("Reading {} (bundled)", path.to_string_lossy());
+        return Ok(bytes.to_vec());
+    }
+    let mut reader = open_reader(path)?;
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(|e| AssetError::new_io(path.to_owned(), false, e))?;
+    Ok(bytes)
+}
+
+enum Preloaded {
+    Bytes(Vec<u8>),
+    Image(Image),
+}
+
+// Files read by a LoadQueue, waiting to be loaded. Each one is removed when it's loaded, so loading it again reads the
+// file from disk.
+static PRELOADED: Mutex<Vec<(PathBuf, Result<Preloaded>)>> = Mutex::new(Vec::new());
+
+fn take_preloaded(path: &Path) -> Option<Result<Preloaded>> {
+    let mut preloaded = PRELOADED.lock().unwrap();
+    let index = preloaded
+        .iter()
+        .position(|(preloaded_path, _)| preloaded_path == path)?;
+    Some(preloaded.swap_remove(index).1)
+}
+fn take_preloaded_bytes(path: &Path) -> Option<Result<Vec<u8>>> {
+    match take_preloaded(path)? {
+        Ok(Preloaded::Bytes(bytes)) => Some(Ok(bytes)),
+        Ok(Preloaded::Image(_)) => None,
+        Err(error) => Some(Err(error)),
+    }
+}
+
+// Reads and decodes files on a worker thread, ahead of loading them. The load functions above then use the file in
+// memory instead of reading it again, so loading code doesn't change. Textures can only be created on the main thread
+// (RenderingContext isn't Send), so images are decoded on the worker and uploaded by load_image_file. Errors are kept
+// until the file is loaded, and returned from there.
+#[derive(Default)]
+pub struct LoadQueue {
+    files: Vec<(PathBuf, bool)>,
+}
+
+impl LoadQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // For load_text_file, load_yaml_file, load_json_file or load_font_file.
+    pub fn file(&mut self, prefix: &str, file: &str) {
+        self.files.push((get_path(prefix, file), false));
+    }
+    // For load_image_file.
+    pub fn image(&mut self, prefix: &str, file: &str) {
+        self.files.push((get_path(prefix, file), true));
+    }
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub(crate) fn start(self) -> Loading {
+        let total = self.files.len();
+        let loaded = Arc::new(AtomicUsize::new(0));
+        let thread = {
+            let loaded = loaded.clone();
+            std::thread::spawn(move || {
+                for (path, image) in self.files {
+                    let result = if image {
+                        decode_image_file(path.clone()).map(Preloaded::Image)
+                    } else {
+                        read_bytes(&path).map(Preloaded::Bytes)
+                    };
+                    PRELOADED.lock().unwrap().push((path, result));
+                    loaded.fetch_add(1, Ordering::Relaxed);
+                }
+            })
+        };
+        Loading {
+            loaded,
+            total,
+            thread: Some(thread),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LoadProgress {
+    pub loaded: usize,
+    pub total: usize,
+}
+
+impl LoadProgress {
+    // From 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.
+        } else {
+            self.loaded as f32 / self.total as f32
+        }
+    }
+}
+
+pub(crate) struct Loading {
+    loaded: Arc<AtomicUsize>,
+    total: usize,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Loading {
+    pub(crate) fn progress(&self) -> LoadProgress {
+        LoadProgress {
+            loaded: self.loaded.load(Ordering::Relaxed),
+            total: self.total,
+        }
+    }
+    // A panic on the worker (which the panic handler already reported) is passed on to the main thread.
+    pub(crate) fn is_finished(&mut self) -> bool {
+        match &self.thread {
+            Some(thread) if thread.is_finished() => {
+                if let Err(panic) = self.thread.take().unwrap().join() {
+                    std::panic::resume_unwind(panic);
+                }
+                true
+            }
+            Some(_) => false,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 1x1 32 bit BMP with bitfields.
+    fn bmp(masks: [u32; 4], pixel: u32) -> Vec<u8> {
+        let mut bytes = b"BM".to_vec();
+        bytes.extend_from_slice(&74u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&70u32.to_le_bytes());
+        bytes.extend_from_slice(&56u32.to_le_bytes());
+        bytes.extend_from_slice(&1i32.to_le_bytes());
+        bytes.extend_from_slice(&1i32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&32u16.to_le_bytes());
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 20]);
+        for mask in masks {
+            bytes.extend_from_slice(&mask.to_le_bytes());
+        }
+        bytes.extend_from_slice(&pixel.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn bmp_bitfields() {
+        let masks = [0xff, 0xff00, 0xff0000, 0xff000000];
+        let image = decode_image(&bmp(masks, 0x80302010)).unwrap();
+        assert_eq!(image.rgba, [0x10, 0x20, 0x30, 0x80]);
+    }
+
+    #[test]
+    fn bmp_zero_mask() {
+        let error = decode_bmp(&bmp([0xff0000, 0, 0xff, 0], 0)).err().unwrap();
+        assert!(error.contains("no bits"), "{}", error);
+    }
+
+    #[test]
+    fn bmp_bad_header() {
+        let masks = [0xff, 0xff00, 0xff0000, 0];
+        for (offset, value) in [(18, 0x7fffffff), (22, 0x7fffffff), (22, 2), (10, u32::MAX)] {
+            let mut bytes = bmp(masks, 0);
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            let error = decode_bmp(&bytes).err().unwrap();
+            assert!(error.contains("truncated"), "{}", error);
+        }
+        let mut huge = bmp(masks, 0);
+        huge[18..26].copy_from_slice(&[0xff, 0xff, 0xff, 0x7f].repeat(2));
+        assert!(decode_bmp(&huge).is_err());
+    }
+
+    fn jpeg(pixels: &[u8], color_type: jpeg_encoder::ColorType) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        jpeg_encoder::Encoder::new(&mut bytes, 100)
+            .encode(pixels, 2, 2, color_type)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn jpeg_rgb() {
+        let pixels = [200, 40, 40].repeat(4);
+        let image = decode_image(&jpeg(&pixels, jpeg_encoder::ColorType::Rgb)).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        for pixel in image.rgba.chunks_exact(4) {
+            assert!(
+                pixel[0].abs_diff(200) < 4 && pixel[1].abs_diff(40) < 4,
+                "{:?}",
+                pixel
+            );
+            assert_eq!(pixel[3], 255);
+        }
+    }
+
+    #[test]
+    fn jpeg_grayscale() {
+        let image = decode_image(&jpeg(&[128; 4], jpeg_encoder::ColorType::Luma)).unwrap();
+        for pixel in image.rgba.chunks_exact(4) {
+            assert!(pixel[0].abs_diff(128) < 2, "{:?}", pixel);
+            assert_eq!(pixel[..3], [pixel[0]; 3]);
+        }
+    }
+
+    #[test]
+    fn jpeg_truncated() {
+        let bytes = jpeg(&[128; 4], jpeg_encoder::ColorType::Luma);
+        assert!(decode_image(&bytes[..bytes.len() / 2]).is_err());
+    }
+}
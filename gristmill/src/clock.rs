@@ -0,0 +1,73 @@
+use std::{sync::Mutex, time::Duration};
+
+// Only one Stage runs per process, so its clock can be global. Games read it with Clock::current.
+static CLOCK: Mutex<Clock> = Mutex::new(Clock {
+    wall_time: Duration::ZERO,
+    time: Duration::ZERO,
+    frames: 0,
+    updates: 0,
+    interpolation: 1.,
+    paused: false,
+});
+
+// Time since the game started. Simulated time advances by the frame time with each fixed update, and stops while
+// paused (no updates run). Wall time is real time, and keeps going while paused.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Clock {
+    wall_time: Duration,
+    time: Duration,
+    frames: u64,
+    updates: u64,
+    interpolation: f32,
+    paused: bool,
+}
+
+impl Clock {
+    pub fn current() -> Clock {
+        *CLOCK.lock().unwrap()
+    }
+    // While paused Game::update isn't called, but events and rendering carry on as usual.
+    pub fn set_paused(paused: bool) {
+        CLOCK.lock().unwrap().paused = paused;
+    }
+
+    pub fn wall_time(&self) -> Duration {
+        self.wall_time
+    }
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+    // Number of frames rendered.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+    // Number of fixed updates run.
+    pub fn updates(&self) -> u64 {
+        self.updates
+    }
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+    // How far the frame is between the last fixed update and the next one, from 0 to 1. Fixed updates don't line up
+    // with frames, so things that move fast can look uneven. To smooth them out, keep each thing's position from
+    // before the last update too, and render it at previous.lerp(current, interpolation). With fps 0 there's an update
+    // every frame, so this is always 1. It doesn't change while paused.
+    pub fn interpolation(&self) -> f32 {
+        self.interpolation
+    }
+
+    pub(crate) fn advance_wall_time(elapsed: Duration) {
+        CLOCK.lock().unwrap().wall_time += elapsed;
+    }
+    pub(crate) fn advance_update(frame_time: Duration) {
+        let mut clock = CLOCK.lock().unwrap();
+        clock.time += frame_time;
+        clock.updates += 1;
+    }
+    pub(crate) fn set_interpolation(interpolation: f32) {
+        CLOCK.lock().unwrap().interpolation = interpolation;
+    }
+    pub(crate) fn advance_frame() {
+        CLOCK.lock().unwrap().frames += 1;
+    }
+}
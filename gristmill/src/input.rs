@@ -1,8 +1,14 @@
 use crate::{
     asset::{self, AssetError},
-    math::Vec2,
+    math::{BVec2, Vec2},
+};
+use serde::{
+    de::{
+        value::{EnumAccessDeserializer, MapAccessDeserializer, SeqAccessDeserializer},
+        EnumAccess, MapAccess, SeqAccess, Visitor,
+    },
+    Deserialize, Deserializer, Serialize, Serializer,
 };
-use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 pub use miniquad::KeyCode;
@@ -17,6 +23,26 @@ pub enum InputState {
 }
 
 impl InputState {
+    // Combines the states of an action's bindings: buttons are pressed if any is pressed, and axes take the value
+    // furthest from zero.
+    fn merge(self, other: InputState) -> InputState {
+        match (self, other) {
+            (InputState::None, state) | (state, InputState::None) => state,
+            (InputState::Button(a), InputState::Button(b)) => InputState::Button(a || b),
+            (InputState::Axis2(a), InputState::Axis2(b)) => {
+                InputState::Axis2(if b.length() > a.length() { b } else { a })
+            }
+            (InputState::Axis2(v), _) | (_, InputState::Axis2(v)) => {
+                eprintln!("Axis2 input can't be combined with other input in one action");
+                InputState::Axis2(v)
+            }
+            (a, b) => {
+                let (a, b) = (a.as_axis1(), b.as_axis1());
+                InputState::Axis1(if b.abs() > a.abs() { b } else { a })
+            }
+        }
+    }
+
     fn as_button(self) -> bool {
         match self {
             InputState::None => false,
@@ -77,11 +103,127 @@ impl TryFrom<miniquad::MouseButton> for MouseButton {
     }
 }
 
+// Named by position, since the labels differ between controllers (South is A on Xbox, Cross on PlayStation).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    West,
+    North,
+    LeftShoulder,
+    RightShoulder,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Modifiers {
+    #[serde(default)]
+    pub shift: bool,
+    #[serde(default)]
+    pub ctrl: bool,
+    #[serde(default)]
+    pub alt: bool,
+    #[serde(default)]
+    pub logo: bool,
+}
+
+impl Modifiers {
+    pub fn is_none(&self) -> bool {
+        *self == Modifiers::default()
+    }
+}
+
+impl From<miniquad::KeyMods> for Modifiers {
+    fn from(mods: miniquad::KeyMods) -> Self {
+        Modifiers {
+            shift: mods.shift,
+            ctrl: mods.ctrl,
+            alt: mods.alt,
+            logo: mods.logo,
+        }
+    }
+}
+
+pub fn gui_key(key: KeyCode) -> Option<silica::Key> {
+    match key {
+        KeyCode::Enter | KeyCode::KpEnter => Some(silica::Key::Enter),
+        KeyCode::Escape => Some(silica::Key::Escape),
+        KeyCode::Up => Some(silica::Key::Up),
+        KeyCode::Down => Some(silica::Key::Down),
+        KeyCode::Left => Some(silica::Key::Left),
+        KeyCode::Right => Some(silica::Key::Right),
+        KeyCode::Backspace => Some(silica::Key::Backspace),
+        KeyCode::Delete => Some(silica::Key::Delete),
+        KeyCode::Home => Some(silica::Key::Home),
+        KeyCode::End => Some(silica::Key::End),
+        _ => None,
+    }
+}
+
 pub enum InputEvent {
-    Key { key: KeyCode, pressed: bool },
-    MouseMotion { position: Vec2 },
-    RawMouseMotion { delta: Vec2 },
-    MouseButton { button: MouseButton, pressed: bool },
+    // Key is the physical key, for controls. Text entry should use Char instead, which is sent after the key down
+    // event when the key (with the modifiers and keyboard layout) produces a character. Both are repeated while
+    // a key is held, with repeat set.
+    Key {
+        key: KeyCode,
+        mods: Modifiers,
+        pressed: bool,
+        repeat: bool,
+    },
+    Char {
+        character: char,
+        mods: Modifiers,
+        repeat: bool,
+    },
+    MouseMotion {
+        position: Vec2,
+    },
+    RawMouseMotion {
+        delta: Vec2,
+    },
+    // Scroll amount as miniquad reports it; y is positive when scrolling up (away from the user).
+    MouseWheel {
+        delta: Vec2,
+    },
+    MouseButton {
+        button: MouseButton,
+        pressed: bool,
+    },
+    // miniquad doesn't read gamepads, so these are never sent by the engine. Games using a gamepad library pass its
+    // events to InputSystem::handle_event as these. Stick values are from -1 to 1 with up positive, like
+    // KeyAxis2Binding, and triggers are from 0 to 1.
+    GamepadButton {
+        button: GamepadButton,
+        pressed: bool,
+    },
+    GamepadAxis {
+        axis: GamepadAxis,
+        value: f32,
+    },
+    // The screen size in physical pixels, sent at startup and whenever the window is resized. InputSystem uses it for
+    // normalized mouse motion.
+    Resize {
+        size: Vec2,
+    },
 }
 
 #[derive(Serialize, Deserialize)]
@@ -214,6 +356,13 @@ enum KeyCodeRemote {
 pub struct KeyBinding {
     #[serde(with = "KeyCodeRemote")]
     key: KeyCode,
+    // The key only presses the binding while exactly these modifiers are held (none by default), so Ctrl+S and S can
+    // be bound separately. With any_mods, the modifiers are ignored. Bindings for modifier keys always ignore them.
+    // Releasing the key always releases the binding.
+    #[serde(default, skip_serializing_if = "Modifiers::is_none")]
+    mods: Modifiers,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    any_mods: bool,
     #[serde(skip)]
     pressed: bool,
 }
@@ -222,13 +371,33 @@ impl KeyBinding {
     pub fn new(key: KeyCode) -> Self {
         KeyBinding {
             key,
+            mods: Modifiers::default(),
+            any_mods: false,
             pressed: false,
         }
     }
+    pub fn with_mods(key: KeyCode, mods: Modifiers) -> Self {
+        KeyBinding {
+            mods,
+            ..Self::new(key)
+        }
+    }
+    pub fn with_any_mods(key: KeyCode) -> Self {
+        KeyBinding {
+            any_mods: true,
+            ..Self::new(key)
+        }
+    }
 
     fn event(&mut self, event: &InputEvent) -> Option<InputState> {
-        if let InputEvent::Key { key, pressed } = *event {
+        if let InputEvent::Key {
+            key, mods, pressed, ..
+        } = *event
+        {
             if key == self.key {
+                if pressed && !self.any_mods && !is_modifier(key) && self.mods != mods {
+                    return None;
+                }
                 self.pressed = pressed;
                 return Some(InputState::Button(self.pressed));
             }
@@ -237,6 +406,7 @@ impl KeyBinding {
     }
 }
 
+// The keys of axis bindings work with any modifiers held, e.g. so holding Shift to run doesn't stop movement.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct KeyAxis1Binding {
     up: KeyBinding,
@@ -246,8 +416,8 @@ pub struct KeyAxis1Binding {
 impl KeyAxis1Binding {
     pub fn new(up: KeyCode, down: KeyCode) -> Self {
         KeyAxis1Binding {
-            up: KeyBinding::new(up),
-            down: KeyBinding::new(down),
+            up: KeyBinding::with_any_mods(up),
+            down: KeyBinding::with_any_mods(down),
         }
     }
 
@@ -284,10 +454,10 @@ pub struct KeyAxis2Binding {
 impl KeyAxis2Binding {
     pub fn new(up: KeyCode, down: KeyCode, left: KeyCode, right: KeyCode) -> Self {
         KeyAxis2Binding {
-            up: KeyBinding::new(up),
-            down: KeyBinding::new(down),
-            left: KeyBinding::new(left),
-            right: KeyBinding::new(right),
+            up: KeyBinding::with_any_mods(up),
+            down: KeyBinding::with_any_mods(down),
+            left: KeyBinding::with_any_mods(left),
+            right: KeyBinding::with_any_mods(right),
         }
     }
 
@@ -348,23 +518,160 @@ impl MouseButtonBinding {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GamepadButtonBinding {
+    button: GamepadButton,
+    #[serde(skip)]
+    pressed: bool,
+}
+
+impl GamepadButtonBinding {
+    pub fn new(button: GamepadButton) -> Self {
+        GamepadButtonBinding {
+            button,
+            pressed: false,
+        }
+    }
+
+    fn event(&mut self, event: &InputEvent) -> Option<InputState> {
+        if let InputEvent::GamepadButton { button, pressed } = *event {
+            if button == self.button {
+                self.pressed = pressed;
+                return Some(InputState::Button(self.pressed));
+            }
+        }
+        None
+    }
+}
+
+// One axis gives an Axis1 state, or two (a stick) an Axis2 state. Values inside the deadzone are zero, and the rest
+// of the range is scaled to start from zero at its edge. For a stick the deadzone is round, so diagonals aren't
+// snapped to the axes.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GamepadAxisBinding {
+    x: GamepadAxis,
+    #[serde(default)]
+    y: Option<GamepadAxis>,
+    #[serde(default)]
+    deadzone: f32,
+    #[serde(skip)]
+    value: Vec2,
+}
+
+impl GamepadAxisBinding {
+    pub fn new(axis: GamepadAxis, deadzone: f32) -> Self {
+        GamepadAxisBinding {
+            x: axis,
+            y: None,
+            deadzone,
+            value: Vec2::ZERO,
+        }
+    }
+    pub fn new_stick(x: GamepadAxis, y: GamepadAxis, deadzone: f32) -> Self {
+        GamepadAxisBinding {
+            y: Some(y),
+            ..Self::new(x, deadzone)
+        }
+    }
+
+    fn event(&mut self, event: &InputEvent) -> Option<InputState> {
+        if let InputEvent::GamepadAxis { axis, value } = *event {
+            if axis == self.x {
+                self.value.x = value;
+            } else if Some(axis) == self.y {
+                self.value.y = value;
+            } else {
+                return None;
+            }
+            return Some(self.state());
+        }
+        None
+    }
+    fn state(&self) -> InputState {
+        let value = if self.y.is_some() {
+            self.value
+        } else {
+            Vec2::new(self.value.x, 0.)
+        };
+        let length = value.length();
+        let value = if length <= self.deadzone {
+            Vec2::ZERO
+        } else {
+            let scaled = ((length - self.deadzone) / (1. - self.deadzone)).min(1.);
+            value * (scaled / length)
+        };
+        if self.y.is_some() {
+            InputState::Axis2(value)
+        } else {
+            InputState::Axis1(value.x)
+        }
+    }
+}
+
+fn default_exponent() -> f32 {
+    1.
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct MouseMotionBinding {
     sensitivity: f32,
+    // Measure motion as a fraction of the screen height instead of in pixels, so it feels the same at any resolution.
+    // The screen size comes from InputEvent::Resize (or InputSystem::set_screen_size); until there is one, motion is
+    // in pixels.
+    #[serde(default)]
+    normalize: bool,
+    // Each motion event shorter than the deadzone is ignored, and the length of the rest (past the deadzone) is raised
+    // to the exponent before the sensitivity is applied. Both are in the same units as the motion (pixels, or screen
+    // heights when normalized), so an exponent above 1 slows small movements and speeds up large ones.
+    #[serde(default)]
+    deadzone: f32,
+    #[serde(default = "default_exponent")]
+    exponent: f32,
     #[serde(skip)]
     motion: Vec2,
+    #[serde(skip)]
+    screen_size: Option<Vec2>,
 }
 
 impl MouseMotionBinding {
     pub fn new(sensitivity: f32) -> Self {
         MouseMotionBinding {
             sensitivity,
+            normalize: false,
+            deadzone: 0.,
+            exponent: 1.,
             motion: Vec2::ZERO,
+            screen_size: None,
+        }
+    }
+    pub fn with_curve(mut self, deadzone: f32, exponent: f32) -> Self {
+        self.deadzone = deadzone;
+        self.exponent = exponent;
+        self
+    }
+    pub fn new_normalized(sensitivity: f32) -> Self {
+        MouseMotionBinding {
+            normalize: true,
+            ..Self::new(sensitivity)
         }
     }
 
+    fn set_screen_size(&mut self, screen_size: Vec2) {
+        self.screen_size = Some(screen_size);
+    }
     fn event(&mut self, event: &InputEvent) -> Option<InputState> {
-        if let InputEvent::RawMouseMotion { delta } = *event {
+        if let InputEvent::RawMouseMotion { mut delta } = *event {
+            if self.normalize {
+                if let Some(screen_size) = self.screen_size {
+                    delta /= screen_size.y;
+                }
+            }
+            let length = delta.length();
+            if length <= self.deadzone {
+                delta = Vec2::ZERO;
+            } else if self.deadzone != 0. || self.exponent != 1. {
+                delta *= (length - self.deadzone).powf(self.exponent) / length;
+            }
             self.motion += delta * self.sensitivity;
             Some(InputState::Axis2(self.motion))
         } else {
@@ -378,15 +685,77 @@ impl MouseMotionBinding {
 }
 
 #[derive(Clone, Serialize, Deserialize)]
-enum Binding {
+pub struct MouseWheelBinding {
+    sensitivity: f32,
+    #[serde(skip)]
+    scroll: Vec2,
+}
+
+impl MouseWheelBinding {
+    pub fn new(sensitivity: f32) -> Self {
+        MouseWheelBinding {
+            sensitivity,
+            scroll: Vec2::ZERO,
+        }
+    }
+
+    fn event(&mut self, event: &InputEvent) -> Option<InputState> {
+        if let InputEvent::MouseWheel { delta } = *event {
+            self.scroll += delta * self.sensitivity;
+            Some(InputState::Axis2(self.scroll))
+        } else {
+            None
+        }
+    }
+    fn end_frame(&mut self) -> InputState {
+        self.scroll = Vec2::ZERO;
+        InputState::Axis2(self.scroll)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Binding {
     Key(KeyBinding),
     KeyAxis1(KeyAxis1Binding),
     KeyAxis2(KeyAxis2Binding),
     MouseButton(MouseButtonBinding),
     MouseMotion(MouseMotionBinding),
+    MouseWheel(MouseWheelBinding),
+    GamepadButton(GamepadButtonBinding),
+    GamepadAxis(GamepadAxisBinding),
 }
 
 impl Binding {
+    // The binding a single input event would make, for letting players pick controls. Only presses (and gamepad axes
+    // pushed at least halfway) make bindings; motion and releases don't.
+    pub fn from_event(event: &InputEvent) -> Option<Binding> {
+        match *event {
+            InputEvent::Key {
+                key,
+                mods,
+                pressed: true,
+                ..
+            } => Some(Binding::Key(KeyBinding::with_mods(key, mods))),
+            InputEvent::MouseButton {
+                button,
+                pressed: true,
+            } => Some(Binding::MouseButton(MouseButtonBinding::new(button))),
+            InputEvent::GamepadButton {
+                button,
+                pressed: true,
+            } => Some(Binding::GamepadButton(GamepadButtonBinding::new(button))),
+            InputEvent::GamepadAxis { axis, value } if value.abs() >= 0.5 => {
+                Some(Binding::GamepadAxis(GamepadAxisBinding::new(axis, 0.2)))
+            }
+            _ => None,
+        }
+    }
+
+    fn set_screen_size(&mut self, screen_size: Vec2) {
+        if let Binding::MouseMotion(binding) = self {
+            binding.set_screen_size(screen_size);
+        }
+    }
     fn event(&mut self, event: &InputEvent) -> Option<InputState> {
         match self {
             Binding::Key(binding) => binding.event(event),
@@ -394,13 +763,59 @@ impl Binding {
             Binding::KeyAxis2(binding) => binding.event(event),
             Binding::MouseButton(binding) => binding.event(event),
             Binding::MouseMotion(binding) => binding.event(event),
+            Binding::MouseWheel(binding) => binding.event(event),
+            Binding::GamepadButton(binding) => binding.event(event),
+            Binding::GamepadAxis(binding) => binding.event(event),
         }
     }
 }
 
+// An action with one binding is written as just the binding, so older files with one binding per action still load.
+#[derive(Clone)]
+struct ActionBindings(Vec<Binding>);
+
+impl Serialize for ActionBindings {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0.len() == 1 {
+            self.0[0].serialize(serializer)
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ActionBindings {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ActionBindingsVisitor)
+    }
+}
+
+struct ActionBindingsVisitor;
+
+impl<'de> Visitor<'de> for ActionBindingsVisitor {
+    type Value = ActionBindings;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a binding or a list of bindings")
+    }
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        Vec::deserialize(SeqAccessDeserializer::new(seq)).map(ActionBindings)
+    }
+    // A single binding is a tagged value in YAML (!Key), or a map with one key in formats like JSON.
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        Binding::deserialize(EnumAccessDeserializer::new(data))
+            .map(|binding| ActionBindings(vec![binding]))
+    }
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        Binding::deserialize(MapAccessDeserializer::new(map))
+            .map(|binding| ActionBindings(vec![binding]))
+    }
+}
+
+// Each action can have several bindings, e.g. a key and a gamepad button, which work at the same time.
 #[derive(Default, Clone, Serialize, Deserialize)]
 #[serde(transparent)]
-pub struct InputBindings(HashMap<String, Binding>);
+pub struct InputBindings(HashMap<String, ActionBindings>);
 
 impl InputBindings {
     pub fn new() -> Self {
@@ -415,6 +830,25 @@ impl InputBindings {
         asset::save_yaml_file("config", Self::FILENAME, self)
     }
 
+    // The control scheme written by InputSystem::create_default_config_if_missing.
+    pub fn default_game_controls() -> Self {
+        let mut bindings = InputBindings::new();
+        bindings.add_mouse_button("primary", MouseButtonBinding::new(MouseButton::Left));
+        bindings.add_mouse_button("secondary", MouseButtonBinding::new(MouseButton::Right));
+        bindings.add_mouse_motion("look", MouseMotionBinding::new(0.01));
+        bindings.add_key("exit", KeyBinding::new(KeyCode::Escape));
+        bindings.add_key_axis2(
+            "move",
+            KeyAxis2Binding::new(KeyCode::W, KeyCode::S, KeyCode::A, KeyCode::D),
+        );
+        bindings.add_key("jump", KeyBinding::new(KeyCode::Space));
+        bindings.add_key_axis1(
+            "fly",
+            KeyAxis1Binding::new(KeyCode::Space, KeyCode::LeftShift),
+        );
+        bindings
+    }
+
     pub fn len(&self) -> usize {
         self.0.len()
     }
@@ -422,20 +856,37 @@ impl InputBindings {
         self.len() == 0
     }
 
+    // Adds to the action's bindings, so calling this again for the same action adds another binding.
+    pub fn add(&mut self, key: &str, binding: Binding) {
+        self.0
+            .entry(key.to_owned())
+            .or_insert_with(|| ActionBindings(Vec::new()))
+            .0
+            .push(binding);
+    }
     pub fn add_key(&mut self, key: &str, binding: KeyBinding) {
-        self.0.insert(key.to_owned(), Binding::Key(binding));
+        self.add(key, Binding::Key(binding));
     }
     pub fn add_key_axis1(&mut self, key: &str, binding: KeyAxis1Binding) {
-        self.0.insert(key.to_owned(), Binding::KeyAxis1(binding));
+        self.add(key, Binding::KeyAxis1(binding));
     }
     pub fn add_key_axis2(&mut self, key: &str, binding: KeyAxis2Binding) {
-        self.0.insert(key.to_owned(), Binding::KeyAxis2(binding));
+        self.add(key, Binding::KeyAxis2(binding));
     }
     pub fn add_mouse_button(&mut self, key: &str, binding: MouseButtonBinding) {
-        self.0.insert(key.to_owned(), Binding::MouseButton(binding));
+        self.add(key, Binding::MouseButton(binding));
     }
     pub fn add_mouse_motion(&mut self, key: &str, binding: MouseMotionBinding) {
-        self.0.insert(key.to_owned(), Binding::MouseMotion(binding));
+        self.add(key, Binding::MouseMotion(binding));
+    }
+    pub fn add_mouse_wheel(&mut self, key: &str, binding: MouseWheelBinding) {
+        self.add(key, Binding::MouseWheel(binding));
+    }
+    pub fn add_gamepad_button(&mut self, key: &str, binding: GamepadButtonBinding) {
+        self.add(key, Binding::GamepadButton(binding));
+    }
+    pub fn add_gamepad_axis(&mut self, key: &str, binding: GamepadAxisBinding) {
+        self.add(key, Binding::GamepadAxis(binding));
     }
 }
 
@@ -443,6 +894,7 @@ impl InputBindings {
 pub struct ActionState {
     changed: bool,
     state: InputState,
+    previous: InputState,
 }
 
 impl ActionState {
@@ -471,6 +923,20 @@ impl ActionState {
     pub fn just_released(&self) -> bool {
         self.released() && self.changed
     }
+    // Which axes moved past the threshold (in either direction) since the last frame. Useful for turning a stick
+    // into discrete menu navigation.
+    pub fn axis2_just_crossed(&self, threshold: f32) -> BVec2 {
+        let previous = self.previous.as_axis2();
+        let current = self.state.as_axis2();
+        let crossed = |previous: f32, current: f32| {
+            current.abs() >= threshold
+                && (previous.abs() < threshold || previous.signum() != current.signum())
+        };
+        BVec2::new(
+            crossed(previous.x, current.x),
+            crossed(previous.y, current.y),
+        )
+    }
 }
 
 #[derive(Default)]
@@ -480,9 +946,48 @@ pub struct PointerState {
     pub secondary: bool,
 }
 
+// While capturing, input goes to the capture instead of actions.
+enum Capture {
+    Off,
+    Waiting,
+    // A modifier key is held. It becomes the binding if it's released without pressing another key.
+    Modifier(KeyCode),
+    Captured(Binding),
+}
+
+fn is_modifier(key: KeyCode) -> bool {
+    matches!(
+        key,
+        KeyCode::LeftShift
+            | KeyCode::RightShift
+            | KeyCode::LeftControl
+            | KeyCode::RightControl
+            | KeyCode::LeftAlt
+            | KeyCode::RightAlt
+            | KeyCode::LeftSuper
+            | KeyCode::RightSuper
+    )
+}
+
+// Key and button releases, and gamepad axes below the threshold that Binding::from_event captures.
+fn is_release(event: &InputEvent) -> bool {
+    match *event {
+        InputEvent::Key { pressed, .. }
+        | InputEvent::MouseButton { pressed, .. }
+        | InputEvent::GamepadButton { pressed, .. } => !pressed,
+        InputEvent::GamepadAxis { value, .. } => value.abs() < 0.5,
+        _ => false,
+    }
+}
+
+// Each binding keeps its own last state, which are merged into the action's state.
+type ActionEntry = (Vec<(Binding, InputState)>, ActionState);
+
 pub struct InputSystem {
-    bindings: HashMap<String, (Binding, ActionState)>,
+    bindings: HashMap<String, ActionEntry>,
     pointer: PointerState,
+    screen_size: Option<Vec2>,
+    capture: Capture,
 }
 
 impl InputSystem {
@@ -490,11 +995,20 @@ impl InputSystem {
         let bindings = bindings
             .0
             .into_iter()
-            .map(|(key, binding)| (key, (binding, ActionState::default())))
+            .map(|(key, bindings)| {
+                let bindings = bindings
+                    .0
+                    .into_iter()
+                    .map(|binding| (binding, InputState::None))
+                    .collect();
+                (key, (bindings, ActionState::default()))
+            })
             .collect();
         InputSystem {
             bindings,
             pointer: Default::default(),
+            screen_size: None,
+            capture: Capture::Off,
         }
     }
     pub fn create_default_config_if_missing() -> asset::Result<()> {
@@ -503,21 +1017,7 @@ impl InputSystem {
             return Ok(());
         }
         println!("Creating default file {}", path.to_string_lossy());
-        let mut bindings = InputBindings::default();
-        bindings.add_mouse_button("primary", MouseButtonBinding::new(MouseButton::Left));
-        bindings.add_mouse_button("secondary", MouseButtonBinding::new(MouseButton::Right));
-        bindings.add_mouse_motion("look", MouseMotionBinding::new(0.01));
-        bindings.add_key("exit", KeyBinding::new(KeyCode::Escape));
-        bindings.add_key_axis2(
-            "move",
-            KeyAxis2Binding::new(KeyCode::W, KeyCode::S, KeyCode::A, KeyCode::D),
-        );
-        bindings.add_key("jump", KeyBinding::new(KeyCode::Space));
-        bindings.add_key_axis1(
-            "fly",
-            KeyAxis1Binding::new(KeyCode::Space, KeyCode::LeftShift),
-        );
-        bindings.save_config()
+        InputBindings::default_game_controls().save_config()
     }
     pub fn load_config() -> asset::Result<Self> {
         Ok(Self::new(InputBindings::load_config()?))
@@ -538,24 +1038,143 @@ impl InputSystem {
         &self.pointer
     }
 
+    // The current bindings, e.g. to save after rebinding.
+    pub fn bindings(&self) -> InputBindings {
+        InputBindings(
+            self.bindings
+                .iter()
+                .map(|(key, (bindings, _))| {
+                    let bindings = bindings.iter().map(|(binding, _)| binding.clone());
+                    (key.clone(), ActionBindings(bindings.collect()))
+                })
+                .collect(),
+        )
+    }
+    // Replaces all of the action's bindings with this one, or adds the action if it isn't bound yet. Returns whether
+    // it was already bound.
+    pub fn rebind(&mut self, action: &str, mut binding: Binding) -> bool {
+        if let Some(screen_size) = self.screen_size {
+            binding.set_screen_size(screen_size);
+        }
+        self.bindings
+            .insert(
+                action.to_owned(),
+                (vec![(binding, InputState::None)], ActionState::default()),
+            )
+            .is_some()
+    }
+
+    // Starts waiting for the next key, mouse button or gamepad input, which is turned into a binding (see
+    // Binding::from_event) and can be taken with captured_binding. Until then, only releases affect actions.
+    // Holding a modifier and pressing a key captures the combination, like Ctrl+S.
+    pub fn capture_next_input(&mut self) {
+        self.capture = Capture::Waiting;
+    }
+    pub fn is_capturing(&self) -> bool {
+        !matches!(self.capture, Capture::Off)
+    }
+    pub fn cancel_capture(&mut self) {
+        self.capture = Capture::Off;
+    }
+    pub fn captured_binding(&mut self) -> Option<Binding> {
+        match std::mem::replace(&mut self.capture, Capture::Off) {
+            Capture::Captured(binding) => Some(binding),
+            capture => {
+                self.capture = capture;
+                None
+            }
+        }
+    }
+    fn capture_event(&mut self, event: &InputEvent) {
+        match (&self.capture, event) {
+            (
+                Capture::Waiting,
+                InputEvent::Key {
+                    key, pressed: true, ..
+                },
+            ) if is_modifier(*key) => self.capture = Capture::Modifier(*key),
+            (
+                Capture::Modifier(modifier),
+                InputEvent::Key {
+                    key,
+                    pressed: false,
+                    ..
+                },
+            ) if key == modifier => {
+                self.capture = Capture::Captured(Binding::Key(KeyBinding::new(*key)));
+            }
+            (Capture::Waiting | Capture::Modifier(_), _) => {
+                if let Some(binding) = Binding::from_event(event) {
+                    self.capture = Capture::Captured(binding);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Handling InputEvent::Resize does this, so it's only needed when the engine's events aren't passed to
+    // handle_event.
+    pub fn set_screen_size(&mut self, width: f32, height: f32) {
+        let screen_size = Vec2::new(width, height);
+        self.screen_size = Some(screen_size);
+        for (bindings, _) in self.bindings.values_mut() {
+            for (binding, _) in bindings.iter_mut() {
+                binding.set_screen_size(screen_size);
+            }
+        }
+    }
+
     pub fn end_frame(&mut self) {
-        // MouseMotionBindings work differently than others. The values are accumulated over each frame, then reset.
-        for (binding, action) in self.bindings.values_mut() {
+        // Mouse motion and wheel bindings work differently than others. The values are accumulated over each frame,
+        // then reset.
+        for (bindings, action) in self.bindings.values_mut() {
             action.changed = false;
-            if let Binding::MouseMotion(binding) = binding {
-                action.state = binding.end_frame();
+            action.previous = action.state;
+            let mut reset = false;
+            for (binding, state) in bindings.iter_mut() {
+                match binding {
+                    Binding::MouseMotion(binding) => *state = binding.end_frame(),
+                    Binding::MouseWheel(binding) => *state = binding.end_frame(),
+                    _ => continue,
+                }
+                reset = true;
+            }
+            if reset {
+                action.state = Self::merged_state(bindings);
             }
         }
     }
 
     pub fn handle_event(&mut self, event: InputEvent) {
-        if let InputEvent::MouseMotion { position } = event {
-            self.pointer.position = position;
-            return;
+        match event {
+            InputEvent::MouseMotion { position } => {
+                self.pointer.position = position;
+                return;
+            }
+            InputEvent::Resize { size } => {
+                self.set_screen_size(size.x, size.y);
+                return;
+            }
+            _ => {}
+        }
+        if self.is_capturing() {
+            self.capture_event(&event);
+            // Releases still go to the bindings, so keys held when capture started don't stay pressed.
+            if !is_release(&event) {
+                return;
+            }
         }
 
-        for (key, (binding, action)) in self.bindings.iter_mut() {
-            if let Some(state) = binding.event(&event) {
+        for (key, (bindings, action)) in self.bindings.iter_mut() {
+            let mut handled = false;
+            for (binding, binding_state) in bindings.iter_mut() {
+                if let Some(state) = binding.event(&event) {
+                    *binding_state = state;
+                    handled = true;
+                }
+            }
+            if handled {
+                let state = Self::merged_state(bindings);
                 if action.state != state {
                     action.state = state;
                     action.changed = true;
@@ -568,4 +1187,247 @@ impl InputSystem {
             }
         }
     }
+    fn merged_state(bindings: &[(Binding, InputState)]) -> InputState {
+        bindings
+            .iter()
+            .fold(InputState::None, |merged, (_, state)| merged.merge(*state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: KeyCode, mods: Modifiers, pressed: bool) -> InputEvent {
+        InputEvent::Key {
+            key,
+            mods,
+            pressed,
+            repeat: false,
+        }
+    }
+    const CTRL: Modifiers = Modifiers {
+        shift: false,
+        ctrl: true,
+        alt: false,
+        logo: false,
+    };
+    const NONE: Modifiers = Modifiers {
+        shift: false,
+        ctrl: false,
+        alt: false,
+        logo: false,
+    };
+
+    #[test]
+    fn exact_modifiers() {
+        let mut plain = KeyBinding::new(KeyCode::S);
+        let mut ctrl = KeyBinding::with_mods(KeyCode::S, CTRL);
+        let mut any = KeyBinding::with_any_mods(KeyCode::S);
+
+        let ctrl_s = key(KeyCode::S, CTRL, true);
+        assert!(plain.event(&ctrl_s).is_none());
+        assert!(ctrl.event(&ctrl_s).is_some() && ctrl.pressed);
+        assert!(any.event(&ctrl_s).is_some() && any.pressed);
+
+        let s = key(KeyCode::S, NONE, true);
+        assert!(plain.event(&s).is_some() && plain.pressed);
+        assert!(ctrl.event(&s).is_none());
+
+        // Releasing always releases, even with different modifiers held.
+        let release = key(KeyCode::S, CTRL, false);
+        for binding in [&mut plain, &mut ctrl, &mut any] {
+            binding.event(&release);
+            assert!(!binding.pressed);
+        }
+    }
+
+    #[test]
+    fn modifier_key_binding() {
+        let mut shift = KeyBinding::new(KeyCode::LeftShift);
+        let mods = Modifiers {
+            shift: true,
+            ..NONE
+        };
+        assert!(shift.event(&key(KeyCode::LeftShift, mods, true)).is_some());
+        assert!(shift.pressed);
+    }
+
+    #[test]
+    fn axis_keys_with_modifiers() {
+        let mut axis = KeyAxis2Binding::new(KeyCode::W, KeyCode::S, KeyCode::A, KeyCode::D);
+        let mods = Modifiers {
+            shift: true,
+            ..NONE
+        };
+        let state = axis.event(&key(KeyCode::W, mods, true));
+        assert_eq!(state, Some(InputState::Axis2(Vec2::new(0., 1.))));
+    }
+
+    #[test]
+    fn binding_config() {
+        let yaml = serde_yml::to_string(&KeyBinding::new(KeyCode::S)).unwrap();
+        assert!(!yaml.contains("mods"), "{}", yaml);
+        let binding: KeyBinding = serde_yml::from_str(&yaml).unwrap();
+        assert!(binding.mods.is_none() && !binding.any_mods);
+
+        let yaml = serde_yml::to_string(&KeyBinding::with_mods(KeyCode::S, CTRL)).unwrap();
+        let binding: KeyBinding = serde_yml::from_str(&yaml).unwrap();
+        assert_eq!(binding.mods, CTRL);
+        let binding: KeyBinding = serde_yml::from_str("key: S\nany_mods: true").unwrap();
+        assert!(binding.any_mods);
+    }
+
+    #[test]
+    fn release_during_capture() {
+        let mut bindings = InputBindings::new();
+        bindings.add_key("jump", KeyBinding::new(KeyCode::Space));
+        let mut input = InputSystem::new(bindings);
+        input.handle_event(key(KeyCode::Space, NONE, true));
+        assert!(input.get("jump").pressed());
+
+        input.capture_next_input();
+        input.handle_event(key(KeyCode::Space, NONE, false));
+        assert!(!input.get("jump").pressed());
+        assert!(input.is_capturing());
+
+        // Presses are captured instead.
+        input.handle_event(key(KeyCode::Space, NONE, true));
+        assert!(!input.get("jump").pressed());
+        assert!(input.captured_binding().is_some());
+    }
+
+    #[test]
+    fn merged_states() {
+        let merge = |a, b| InputState::merge(a, b);
+        assert_eq!(
+            merge(InputState::Button(false), InputState::Button(true)),
+            InputState::Button(true)
+        );
+        assert_eq!(
+            merge(InputState::None, InputState::Button(false)),
+            InputState::Button(false)
+        );
+        assert_eq!(
+            merge(InputState::Axis1(-0.8), InputState::Axis1(0.5)),
+            InputState::Axis1(-0.8)
+        );
+        assert_eq!(
+            merge(InputState::Button(true), InputState::Axis1(0.5)),
+            InputState::Axis1(1.)
+        );
+        let (short, long) = (Vec2::new(0.3, 0.), Vec2::new(0., -0.9));
+        assert_eq!(
+            merge(InputState::Axis2(short), InputState::Axis2(long)),
+            InputState::Axis2(long)
+        );
+        // Axis2 can't be combined with anything else, so it wins.
+        assert_eq!(
+            merge(InputState::Axis1(1.), InputState::Axis2(short)),
+            InputState::Axis2(short)
+        );
+    }
+
+    #[test]
+    fn legacy_single_binding() {
+        let yaml = "jump: !Key\n  key: Space\nlook: !MouseMotion\n  sensitivity: 0.01\n";
+        // Config files are loaded through a Value, to fill in missing keys.
+        let value: serde_yml::Value = serde_yml::from_str(yaml).unwrap();
+        let bindings: InputBindings = serde_yml::from_value(value).unwrap();
+        assert_eq!(bindings.0["jump"].0.len(), 1);
+        let bindings: InputBindings = serde_yml::from_str(yaml).unwrap();
+        let mut input = InputSystem::new(bindings);
+        input.handle_event(key(KeyCode::Space, NONE, true));
+        assert!(input.get("jump").pressed());
+    }
+
+    #[test]
+    fn several_bindings_round_trip() {
+        let mut bindings = InputBindings::new();
+        bindings.add_key("jump", KeyBinding::new(KeyCode::Space));
+        bindings.add_gamepad_button("jump", GamepadButtonBinding::new(GamepadButton::South));
+        bindings.add_key("exit", KeyBinding::new(KeyCode::Escape));
+        let yaml = serde_yml::to_string(&bindings).unwrap();
+        assert!(yaml.contains("jump:\n- !Key"), "{}", yaml);
+        assert!(yaml.contains("exit: !Key"), "{}", yaml);
+
+        let bindings: InputBindings = serde_yml::from_str(&yaml).unwrap();
+        assert_eq!(bindings.0["jump"].0.len(), 2);
+        let mut input = InputSystem::new(bindings);
+        input.handle_event(InputEvent::GamepadButton {
+            button: GamepadButton::South,
+            pressed: true,
+        });
+        assert!(input.get("jump").pressed());
+    }
+
+    #[test]
+    fn keyboard_and_gamepad_merge() {
+        let mut bindings = InputBindings::new();
+        bindings.add_key_axis1("steer", KeyAxis1Binding::new(KeyCode::D, KeyCode::A));
+        bindings.add_gamepad_axis(
+            "steer",
+            GamepadAxisBinding::new(GamepadAxis::LeftStickX, 0.),
+        );
+        let mut input = InputSystem::new(bindings);
+        input.handle_event(InputEvent::GamepadAxis {
+            axis: GamepadAxis::LeftStickX,
+            value: -0.5,
+        });
+        assert_eq!(input.get("steer").axis1_state(), -0.5);
+        input.handle_event(key(KeyCode::D, NONE, true));
+        assert_eq!(input.get("steer").axis1_state(), 1.);
+        input.handle_event(key(KeyCode::D, NONE, false));
+        assert_eq!(input.get("steer").axis1_state(), -0.5);
+    }
+
+    #[test]
+    fn motion_reset_keeps_keys() {
+        let mut bindings = InputBindings::new();
+        bindings.add_key_axis2(
+            "look",
+            KeyAxis2Binding::new(KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right),
+        );
+        bindings.add_mouse_motion("look", MouseMotionBinding::new(0.5));
+        let mut input = InputSystem::new(bindings);
+        input.handle_event(InputEvent::RawMouseMotion {
+            delta: Vec2::new(4., 0.),
+        });
+        assert_eq!(input.get("look").axis2_state(), Vec2::new(2., 0.));
+        input.end_frame();
+        assert_eq!(input.get("look").axis2_state(), Vec2::ZERO);
+
+        // A held key outlasts the reset, and is replaced by larger motion while it lasts.
+        input.handle_event(key(KeyCode::Up, NONE, true));
+        input.handle_event(InputEvent::RawMouseMotion {
+            delta: Vec2::new(4., 0.),
+        });
+        assert_eq!(input.get("look").axis2_state(), Vec2::new(2., 0.));
+        input.end_frame();
+        assert_eq!(input.get("look").axis2_state(), Vec2::new(0., 1.));
+    }
+
+    #[test]
+    fn normalized_motion() {
+        let mut bindings = InputBindings::new();
+        bindings.add_mouse_motion("look", MouseMotionBinding::new_normalized(2.));
+        let mut input = InputSystem::new(bindings);
+        input.handle_event(InputEvent::Resize {
+            size: Vec2::new(1600., 1000.),
+        });
+        input.handle_event(InputEvent::RawMouseMotion {
+            delta: Vec2::new(100., -50.),
+        });
+        assert_eq!(input.get("look").axis2_state(), Vec2::new(0.2, -0.1));
+
+        // Rebinding keeps the screen size.
+        input.rebind(
+            "look",
+            Binding::MouseMotion(MouseMotionBinding::new_normalized(1.)),
+        );
+        input.handle_event(InputEvent::RawMouseMotion {
+            delta: Vec2::new(500., 0.),
+        });
+        assert_eq!(input.get("look").axis2_state(), Vec2::new(0.5, 0.));
+    }
 }
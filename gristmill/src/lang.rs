@@ -1,22 +1,180 @@
-use std::{collections::HashMap, sync::OnceLock};
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Mutex, RwLock},
+};
 
 use crate::asset;
 
-static TRANSLATIONS: OnceLock<HashMap<String, String>> = OnceLock::new();
+const FALLBACK_LOCALE: &str = "en";
+
+// Strings are leaked so tr can hand out references while the locale can still be switched. They're interned, so
+// loading the same strings again (switching back to a locale, or loading the translations again) doesn't leak more.
+type Strings = HashMap<String, &'static str>;
+
+struct Translations {
+    locale: String,
+    loaded: HashMap<String, Strings>,
+}
+
+static TRANSLATIONS: RwLock<Option<Translations>> = RwLock::new(None);
+static INTERNED: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+fn intern(value: String) -> &'static str {
+    let mut interned = INTERNED.lock().unwrap();
+    if let Some(existing) = interned.get(value.as_str()) {
+        return existing;
+    }
+    let leaked = Box::leak(value.into_boxed_str());
+    interned.insert(leaked);
+    leaked
+}
+
+// The locale from GRISTMILL_LOCALE if it's set, otherwise the system's: LANG and friends on Linux, or the display
+// language on Windows and macOS.
+fn system_locale() -> Option<String> {
+    std::env::var("GRISTMILL_LOCALE")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .or_else(sys_locale::get_locale)
+        .map(|value| locale_name(&value))
+        .filter(|locale| locale != "C" && locale != "POSIX")
+}
+
+// The name lang files use, e.g. de_DE for de-DE, de_DE.UTF-8 or de_DE@euro.
+fn locale_name(value: &str) -> String {
+    let end = value.find(['.', '@']).unwrap_or(value.len());
+    value[..end].replace('-', "_")
+}
+
+// A locale like de_DE uses lang/de_DE.yaml, or lang/de.yaml if there isn't one.
+fn locale_file(locale: &str) -> Option<(String, String)> {
+    let language = locale.split('_').next().unwrap_or(locale);
+    [locale, language]
+        .into_iter()
+        .map(|name| (name.to_owned(), format!("{}.yaml", name)))
+        .find(|(_, file)| asset::exists("lang", file))
+}
+
+fn load_strings(file: &str) -> asset::Result<Strings> {
+    let strings: HashMap<String, String> = asset::load_yaml_file("lang", file)?;
+    Ok(strings
+        .into_iter()
+        .map(|(key, value)| (key, intern(value)))
+        .collect())
+}
 
 pub fn load_translations() -> asset::Result<()> {
-    // TODO use correct locale
-    let translations = asset::load_yaml_file("lang", "en.yaml")?;
-    TRANSLATIONS.get_or_init(|| translations);
+    let mut loaded = HashMap::new();
+    loaded.insert(
+        FALLBACK_LOCALE.to_owned(),
+        load_strings(&format!("{}.yaml", FALLBACK_LOCALE))?,
+    );
+    let mut locale = FALLBACK_LOCALE.to_owned();
+    if let Some((name, file)) = system_locale().as_deref().and_then(locale_file) {
+        if !loaded.contains_key(&name) {
+            loaded.insert(name.clone(), load_strings(&file)?);
+        }
+        locale = name;
+    }
+    *TRANSLATIONS.write().unwrap() = Some(Translations { locale, loaded });
     Ok(())
 }
 
+// The locale whose file is in use, e.g. de when asking for de_DE and there's only lang/de.yaml.
+pub fn locale() -> String {
+    let translations = TRANSLATIONS.read().unwrap();
+    let translations = translations.as_ref().expect("translations not loaded");
+    translations.locale.clone()
+}
+
+// Switches to another language, e.g. from an in-game language selector. Strings already returned by tr aren't
+// updated, so get them again after switching.
+pub fn set_locale(locale: &str) -> asset::Result<()> {
+    let (name, file) = match locale_file(locale) {
+        Some(found) => found,
+        None => (locale.to_owned(), format!("{}.yaml", locale)),
+    };
+    let mut translations = TRANSLATIONS.write().unwrap();
+    let translations = translations.as_mut().expect("translations not loaded");
+    if !translations.loaded.contains_key(&name) {
+        // Fails with the missing file if neither exists.
+        let strings = load_strings(&file)?;
+        translations.loaded.insert(name.clone(), strings);
+    }
+    translations.locale = name;
+    Ok(())
+}
+
+// Which of the keys the current locale doesn't have (even if English has them).
+pub fn validate_keys(expected: &[&str]) -> Vec<String> {
+    let translations = TRANSLATIONS.read().unwrap();
+    let translations = translations.as_ref().expect("translations not loaded");
+    let strings = &translations.loaded[&translations.locale];
+    expected
+        .iter()
+        .filter(|key| !strings.contains_key(**key))
+        .map(|key| (*key).to_owned())
+        .collect()
+}
+
+// Checks every file in lang/ for the keys, giving a line for each locale that's missing some.
+pub(crate) fn validate_all_locales(expected: &[&str]) -> asset::Result<Vec<String>> {
+    let mut lang_dir = asset::base_path();
+    lang_dir.push("lang");
+    let mut files: Vec<String> = std::fs::read_dir(&lang_dir)
+        .map_err(|e| asset::AssetError::new_io(lang_dir, false, e))?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|file| file.ends_with(".yaml"))
+        .collect();
+    files.sort();
+    let mut problems = Vec::new();
+    for file in files {
+        let strings: HashMap<String, String> = asset::load_yaml_file("lang", &file)?;
+        let missing: Vec<&str> = expected
+            .iter()
+            .copied()
+            .filter(|key| !strings.contains_key(*key))
+            .collect();
+        if !missing.is_empty() {
+            problems.push(format!("{} is missing {}", file, missing.join(", ")));
+        }
+    }
+    Ok(problems)
+}
+
+// Looks the key up in the current locale, then in English.
 pub fn tr(key: &str) -> &str {
-    let translations = TRANSLATIONS.get().expect("translations not loaded");
-    if let Some(value) = translations.get(key) {
+    let translations = TRANSLATIONS.read().unwrap();
+    let translations = translations.as_ref().expect("translations not loaded");
+    let value = [translations.locale.as_str(), FALLBACK_LOCALE]
+        .iter()
+        .filter_map(|locale| translations.loaded.get(*locale))
+        .find_map(|strings| strings.get(key).copied());
+    if let Some(value) = value {
         value
     } else {
         eprintln!("Missing translation for {}", key);
         key
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_names() {
+        assert_eq!(locale_name("de_DE.UTF-8"), "de_DE");
+        assert_eq!(locale_name("de_DE@euro"), "de_DE");
+        assert_eq!(locale_name("pt-BR"), "pt_BR");
+        assert_eq!(locale_name("fr"), "fr");
+    }
+
+    #[test]
+    fn interned_once() {
+        let a = intern("Neues Spiel".to_owned());
+        let b = intern("Neues Spiel".to_owned());
+        assert!(std::ptr::eq(a, b));
+        assert!(!std::ptr::eq(a, intern("Spiel laden".to_owned())));
+    }
+}
@@ -1,43 +1,139 @@
 pub mod asset;
+mod clock;
 pub mod input;
 mod lang;
+pub mod timer;
 pub mod two;
 
+pub use clock::Clock;
 pub use glam as math;
 pub use grist::*;
-pub use lang::tr;
+pub use lang::{locale, set_locale, tr, validate_keys};
 pub use palette as color;
 
 use miniquad::*;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::VecDeque,
     fs::OpenOptions,
     path::PathBuf,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     time::{Duration, Instant},
 };
 use tiny_game_loop::GameLoop;
 
 use input::InputEvent;
 use math::Vec2;
-use two::QuadRenderer;
+use two::{QuadRenderer, QuadRendererBuilder};
 
 pub mod window {
-    pub use miniquad::window::{order_quit, request_quit, screen_size};
+    pub use miniquad::window::{dpi_scale, order_quit, request_quit, screen_size};
+    pub use silica::CursorIcon;
+
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    };
+
+    use crate::{asset, WindowConfig, CONTEXT_CREATED};
+
+    pub(crate) static FULLSCREEN: AtomicBool = AtomicBool::new(false);
+    static CURSOR_ICON: Mutex<CursorIcon> = Mutex::new(CursorIcon::Default);
+
+    pub fn is_fullscreen() -> bool {
+        FULLSCREEN.load(Ordering::Relaxed)
+    }
+    // Window changes take effect right away, with the game getting the new size through Game::set_screen_size. They're
+    // also saved to window.yaml so the game starts the same way next time; the window still changes if saving fails.
+    pub fn set_fullscreen(fullscreen: bool) -> asset::Result<()> {
+        miniquad::window::set_fullscreen(fullscreen);
+        FULLSCREEN.store(fullscreen, Ordering::Relaxed);
+        WindowConfig::save_changes(|config| config.fullscreen = fullscreen)
+    }
+    pub fn set_window_size(width: u32, height: u32) -> asset::Result<()> {
+        miniquad::window::set_window_size(width, height);
+        WindowConfig::save_changes(|config| {
+            config.width = width;
+            config.height = height;
+        })
+    }
+
+    // None if the clipboard is empty, doesn't hold text, or isn't available (on the web, or before the window opens).
+    pub fn get_clipboard() -> Option<String> {
+        if !CONTEXT_CREATED.load(Ordering::SeqCst) {
+            return None;
+        }
+        miniquad::window::clipboard_get()
+    }
+    // Does nothing if the clipboard isn't available.
+    pub fn set_clipboard(text: &str) {
+        if CONTEXT_CREATED.load(Ordering::SeqCst) {
+            miniquad::window::clipboard_set(text);
+        }
+    }
+
+    // For GUI cursors, call this each frame with Gui::cursor_icon. The cursor is only changed when it's different.
+    pub fn set_cursor_icon(icon: CursorIcon) {
+        let mut current = CURSOR_ICON.lock().unwrap();
+        if *current != icon && CONTEXT_CREATED.load(Ordering::SeqCst) {
+            *current = icon;
+            miniquad::window::set_mouse_cursor(match icon {
+                CursorIcon::Default => miniquad::CursorIcon::Default,
+                CursorIcon::Help => miniquad::CursorIcon::Help,
+                CursorIcon::Pointer => miniquad::CursorIcon::Pointer,
+                CursorIcon::Wait => miniquad::CursorIcon::Wait,
+                CursorIcon::Crosshair => miniquad::CursorIcon::Crosshair,
+                CursorIcon::Text => miniquad::CursorIcon::Text,
+                CursorIcon::Move => miniquad::CursorIcon::Move,
+                CursorIcon::NotAllowed => miniquad::CursorIcon::NotAllowed,
+                CursorIcon::EWResize => miniquad::CursorIcon::EWResize,
+                CursorIcon::NSResize => miniquad::CursorIcon::NSResize,
+                CursorIcon::NESWResize => miniquad::CursorIcon::NESWResize,
+                CursorIcon::NWSEResize => miniquad::CursorIcon::NWSEResize,
+            });
+        }
+    }
+    // To draw a custom cursor, hide the system one and queue a sprite at the pointer position after everything else.
+    pub fn show_cursor(shown: bool) {
+        if CONTEXT_CREATED.load(Ordering::SeqCst) {
+            miniquad::window::show_mouse(shown);
+        }
+    }
 }
 
 pub type RenderingContext = Box<dyn RenderingBackend>;
 pub use glyph_brush::ab_glyph::FontArc as Font;
 
-static DROPPED_TEXTURES: Mutex<Vec<TextureId>> = Mutex::new(Vec::new());
+// Texture ids are only meaningful to the context that created them. Only one Stage (and so one context) is ever
+// created per process, which is what makes it safe to queue dropped textures globally.
+// Render target textures are dropped along with their render pass.
+static DROPPED_TEXTURES: Mutex<Vec<(TextureId, Option<RenderPass>)>> = Mutex::new(Vec::new());
+static CONTEXT_CREATED: AtomicBool = AtomicBool::new(false);
+static INVALID_TEXTURE: Mutex<Option<Texture>> = Mutex::new(None);
 
-#[derive(PartialEq, Eq, Hash)]
-struct TextureHandle(TextureId);
+struct TextureHandle(TextureId, Option<RenderPass>);
+
+impl PartialEq for TextureHandle {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for TextureHandle {}
+
+impl std::hash::Hash for TextureHandle {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
 
 impl Drop for TextureHandle {
     fn drop(&mut self) {
         let mut dropped = DROPPED_TEXTURES.lock().unwrap();
-        dropped.push(self.0);
+        dropped.push((self.0, self.1));
     }
 }
 
@@ -51,16 +147,97 @@ impl Texture {
         height: u16,
         bytes: &[u8],
     ) -> Self {
-        let handle = TextureHandle(context.new_texture_from_rgba8(width, height, bytes));
+        let handle = TextureHandle(context.new_texture_from_rgba8(width, height, bytes), None);
         Texture(Arc::new(handle))
     }
+    // Decodes a PNG, BMP or JPEG image in memory, e.g. one embedded with include_bytes!.
+    pub fn from_image_bytes(context: &mut RenderingContext, bytes: &[u8]) -> asset::Result<Self> {
+        asset::decode_image(bytes)
+            .map(|image| image.into_texture(context))
+            .map_err(|info| asset::AssetError::new_format("(image bytes)".into(), false, info))
+    }
     pub fn new_invalid(context: &mut RenderingContext) -> Self {
         Self::new_rgba8(context, 1, 1, &[255, 0, 255, 255])
     }
+    // A single invalid texture shared by everything, created the first time it's needed.
+    pub fn invalid(context: &mut RenderingContext) -> Self {
+        let mut invalid = INVALID_TEXTURE.lock().unwrap();
+        invalid
+            .get_or_insert_with(|| Self::new_invalid(context))
+            .clone()
+    }
 
     pub fn id(&self) -> TextureId {
         self.0 .0
     }
+    pub fn size(&self, context: &RenderingContext) -> (u32, u32) {
+        context.texture_size(self.id())
+    }
+    // Reads the texture back from the GPU as RGBA8, rows from the top. This stalls the pipeline, so it's slow and
+    // meant for load time (e.g. building a collision mask from the alpha channel), and like all rendering it only
+    // works on the main thread. Textures are always created as RGBA8, which can be read back directly.
+    pub fn read_pixels(&self, context: &mut RenderingContext) -> Vec<u8> {
+        let (width, height) = self.size(context);
+        let mut bytes = vec![0; width as usize * height as usize * 4];
+        context.texture_read_pixels(self.id(), &mut bytes);
+        bytes
+    }
+}
+
+// An off-screen texture that QuadRenderer::render_to draws into, for post-processing, minimaps, or GUI on a texture.
+// The color texture can be drawn like any other texture. A screen sized target follows the renderer's screen size,
+// and is resized when it's drawn into.
+#[derive(Clone)]
+pub struct RenderTarget {
+    texture: Texture,
+    screen_sized: bool,
+}
+
+impl RenderTarget {
+    pub fn new(context: &mut RenderingContext, width: u32, height: u32) -> Self {
+        let id = context.new_render_texture(TextureParams {
+            kind: TextureKind::Texture2D,
+            format: TextureFormat::RGBA8,
+            wrap: TextureWrap::Clamp,
+            min_filter: FilterMode::Linear,
+            mag_filter: FilterMode::Linear,
+            mipmap_filter: MipmapFilterMode::None,
+            width: width.max(1),
+            height: height.max(1),
+            allocate_mipmaps: false,
+        });
+        let pass = context.new_render_pass(id, None);
+        RenderTarget {
+            texture: Texture(Arc::new(TextureHandle(id, Some(pass)))),
+            screen_sized: false,
+        }
+    }
+    pub fn screen_sized(context: &mut RenderingContext) -> Self {
+        let (width, height) = window::screen_size();
+        let mut target = Self::new(context, width as u32, height as u32);
+        target.screen_sized = true;
+        target
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+    pub fn pass(&self) -> RenderPass {
+        self.texture.0 .1.unwrap()
+    }
+    pub fn is_screen_sized(&self) -> bool {
+        self.screen_sized
+    }
+    pub fn size(&self, context: &RenderingContext) -> (u32, u32) {
+        self.texture.size(context)
+    }
+    // Clears the contents if the size changes.
+    pub fn resize(&mut self, context: &mut RenderingContext, width: u32, height: u32) {
+        let size = (width.max(1), height.max(1));
+        if self.size(context) != size {
+            context.texture_resize(self.texture.id(), size.0, size.1, None);
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -68,7 +245,21 @@ struct WindowConfig {
     width: u32,
     height: u32,
     fullscreen: bool,
+    // Fixed updates per second, or 0 for one update per frame (with the frame rate left to vsync).
     fps: u32,
+    #[serde(default = "WindowConfig::default_vsync")]
+    vsync: bool,
+    #[serde(default)]
+    high_dpi: bool,
+    #[serde(default)]
+    smooth_frame_time: bool,
+    #[serde(default)]
+    borderless: bool,
+    #[serde(default)]
+    always_on_top: bool,
+    // Skip updates while the window is minimized, like Clock::set_paused (but Clock::paused doesn't change).
+    #[serde(default)]
+    pause_when_minimized: bool,
 }
 
 impl Default for WindowConfig {
@@ -78,15 +269,27 @@ impl Default for WindowConfig {
             height: 600,
             fullscreen: false,
             fps: 60,
+            vsync: true,
+            high_dpi: false,
+            smooth_frame_time: false,
+            borderless: false,
+            always_on_top: false,
+            pause_when_minimized: false,
         }
     }
 }
 
 impl WindowConfig {
     const FILENAME: &'static str = "window.yaml";
+    fn default_vsync() -> bool {
+        true
+    }
     fn create_default_config_if_missing() -> asset::Result<()> {
         let path = asset::get_path("config", Self::FILENAME);
         if path.exists() {
+            if asset::upgrade_yaml_file::<Self>("config", Self::FILENAME)? {
+                println!("Added new settings to {}", path.to_string_lossy());
+            }
             return Ok(());
         }
         println!("Creating default file {}", path.to_string_lossy());
@@ -95,6 +298,50 @@ impl WindowConfig {
     fn load_config() -> asset::Result<Self> {
         asset::load_yaml_file("config", Self::FILENAME)
     }
+    // Loads the file again, so only the changed settings are saved (not ones edited in the file since startup).
+    fn save_changes(change: impl FnOnce(&mut Self)) -> asset::Result<()> {
+        let mut config = Self::load_config()?;
+        change(&mut config);
+        asset::save_yaml_file("config", Self::FILENAME, &config)
+    }
+    // miniquad can only remove the title bar on Wayland, when the compositor leaves drawing it to the app (like
+    // GNOME), so a borderless window uses the Wayland backend in a Wayland session. It can't keep windows on top at
+    // all. Settings that can't be honored are returned as notes and ignored, so the game still starts.
+    fn to_conf(&self, window_title: &str) -> (conf::Conf, Vec<&'static str>) {
+        let mut platform = conf::Platform::default();
+        let mut notes = Vec::new();
+        if self.borderless {
+            if self.fullscreen {
+                notes.push("borderless has no effect on a fullscreen window");
+            } else if cfg!(target_os = "linux") && std::env::var_os("WAYLAND_DISPLAY").is_some() {
+                platform.linux_backend = conf::LinuxBackend::WaylandWithX11Fallback;
+                platform.wayland_use_fallback_decorations = false;
+            } else {
+                notes.push("borderless windows are only supported in Wayland sessions");
+            }
+        }
+        if self.always_on_top {
+            notes.push("always_on_top is not supported on this platform");
+        }
+        // Only a hint, which some drivers ignore.
+        platform.swap_interval = Some(if self.vsync { 1 } else { 0 });
+        let conf = conf::Conf {
+            window_title: window_title.to_string(),
+            window_width: self.width.try_into().unwrap(),
+            window_height: self.height.try_into().unwrap(),
+            fullscreen: self.fullscreen,
+            high_dpi: self.high_dpi,
+            window_resizable: false,
+            platform,
+            ..Default::default()
+        };
+        (conf, notes)
+    }
+}
+
+pub struct DroppedFile {
+    pub path: Option<PathBuf>,
+    pub bytes: Option<Vec<u8>>,
 }
 
 pub trait Game: Sized + 'static {
@@ -103,33 +350,115 @@ pub trait Game: Sized + 'static {
     fn quit_requested(&mut self) -> bool {
         true
     }
+    // miniquad only reports minimize/restore, so that's what focus changes are based on. To pause the game here (or
+    // from a pause menu) use Clock::set_paused, or set pause_when_minimized in window.yaml.
+    fn focus_event(&mut self, _focused: bool) {}
+    fn files_dropped(&mut self, _files: Vec<DroppedFile>) {}
+    // Called at startup and whenever the DPI scale changes. Screen sizes and input positions are in physical pixels.
+    fn dpi_scale_changed(&mut self, _dpi_scale: f32) {}
     fn update(&mut self, frame_time: Duration);
+    // Called once per frame after the fixed updates, with whether any of them ran.
+    fn post_update(&mut self, _did_update: bool) {}
+    // See Clock::interpolation for smoothing out movement between fixed updates.
     fn render(&mut self, context: &mut RenderingContext);
+    // Called after render, before the frame is presented. Useful for full-screen effects and transitions.
+    fn post_render(&mut self, _context: &mut RenderingContext) {}
 }
 
 pub trait GameLoader: 'static {
     type Assets;
     type Game: Game;
     fn fonts() -> Vec<&'static str>;
+    // Every key the game passes to tr. In debug builds, lang files missing any of them are reported while loading.
+    fn translation_keys() -> Vec<&'static str> {
+        Vec::new()
+    }
     fn create_default_files() -> asset::Result<()>;
+    // Files to read on a worker thread before load is called, while the window shows render_loading. load then gets
+    // them from memory, so it doesn't need to change. With nothing queued, load runs before the window shows anything.
+    fn queue_assets(_queue: &mut asset::LoadQueue) {}
+    // Drawn each frame while queued assets load, finishing with QuadRenderer::render_pass.
+    fn render_loading(
+        _renderer: &mut QuadRenderer,
+        _context: &mut RenderingContext,
+        _progress: asset::LoadProgress,
+    ) {
+    }
     fn load(context: &mut RenderingContext) -> asset::Result<Self::Assets>;
+    // Load missing or broken image files as the invalid texture, instead of failing. See asset::load_image_file.
+    fn invalid_texture_fallback() -> bool {
+        false
+    }
+    fn prewarm_glyphs(_renderer: &mut QuadRenderer, _context: &mut RenderingContext) {}
     fn create_game(renderer: QuadRenderer, assets: Self::Assets) -> Self::Game;
 }
 
+// Averages the frame time over a few frames, so a single slow frame doesn't cause one big jump. The difference from
+// the real frame times is carried over to the next frames, so game time doesn't drift away from real time.
+#[derive(Default)]
+struct FrameTimeSmoother {
+    history: VecDeque<f64>,
+    drift: f64,
+}
+
+impl FrameTimeSmoother {
+    const FRAMES: usize = 8;
+
+    fn smooth(&mut self, elapsed: Duration) -> Duration {
+        let elapsed = elapsed.as_secs_f64();
+        if self.history.len() == Self::FRAMES {
+            self.history.pop_front();
+        }
+        self.history.push_back(elapsed);
+        let average = self.history.iter().sum::<f64>() / self.history.len() as f64;
+        let smoothed = (average + self.drift / Self::FRAMES as f64).max(0.);
+        self.drift += elapsed - smoothed;
+        Duration::from_secs_f64(smoothed)
+    }
+}
+
 struct Stage<G: Game> {
     context: RenderingContext,
-    game_loop: GameLoop,
+    // None when fps is 0.
+    game_loop: Option<GameLoop>,
+    frame_time_smoother: Option<FrameTimeSmoother>,
     time: Instant,
+    pause_when_minimized: bool,
+    minimized: bool,
+    paused: bool,
+    // Set when the window is restored, since the platform may not have run updates while it was minimized.
+    resumed: bool,
+    dpi_scale: f32,
     game: G,
 }
 
+// Tells the game about the screen size, and its input handling too (for normalized mouse motion).
+fn resize_game(game: &mut impl Game, width: f32, height: f32) {
+    game.set_screen_size(width, height);
+    game.handle_event(InputEvent::Resize {
+        size: Vec2::new(width, height),
+    });
+}
+
 impl<G: Game> Stage<G> {
     fn new(mut game: G, context: RenderingContext, window_config: WindowConfig) -> Self {
-        game.set_screen_size(window_config.width as f32, window_config.height as f32);
+        let (width, height) = miniquad::window::screen_size();
+        let dpi_scale = miniquad::window::dpi_scale();
+        game.dpi_scale_changed(dpi_scale);
+        resize_game(&mut game, width, height);
         Stage {
             context,
-            game_loop: GameLoop::new_with_fps(window_config.fps, Duration::from_millis(250)),
+            game_loop: (window_config.fps > 0)
+                .then(|| GameLoop::new_with_fps(window_config.fps, Duration::from_millis(250))),
+            frame_time_smoother: window_config
+                .smooth_frame_time
+                .then(FrameTimeSmoother::default),
             time: Instant::now(),
+            pause_when_minimized: window_config.pause_when_minimized,
+            minimized: false,
+            paused: false,
+            resumed: false,
+            dpi_scale,
             game,
         }
     }
@@ -139,25 +468,67 @@ impl<G: Game> EventHandler for Stage<G> {
     fn update(&mut self) {
         let elapsed = self.time.elapsed();
         self.time = Instant::now();
-        let update = self.game_loop.update(elapsed);
-        if update.num_updates > 0 {
-            update.run(|update| self.game.update(update.frame_time));
+        Clock::advance_wall_time(elapsed);
+        let paused = Clock::current().paused() || (self.minimized && self.pause_when_minimized);
+        let resumed = std::mem::take(&mut self.resumed) || (self.paused && !paused);
+        self.paused = paused;
+        // The time spent paused or minimized isn't caught up on (or averaged into later frames), so updates continue
+        // smoothly from where they stopped.
+        let frame_time = if resumed {
+            if let Some(smoother) = &mut self.frame_time_smoother {
+                *smoother = FrameTimeSmoother::default();
+            }
+            Duration::ZERO
+        } else if let Some(smoother) = &mut self.frame_time_smoother {
+            smoother.smooth(elapsed)
         } else {
-            // Limit framerate
-            std::thread::sleep(update.frame_time - elapsed);
-        }
+            elapsed
+        };
+        let did_update = match &mut self.game_loop {
+            Some(game_loop) => {
+                let update = game_loop.update(frame_time);
+                let did_update = update.num_updates > 0 && !paused;
+                if !paused {
+                    Clock::set_interpolation(update.blending_factor as f32);
+                }
+                if did_update {
+                    update.run(|update| {
+                        Clock::advance_update(update.frame_time);
+                        self.game.update(update.frame_time);
+                    });
+                } else if update.num_updates == 0 {
+                    // Limit framerate. A long frame can take more than the frame time.
+                    std::thread::sleep(update.frame_time.saturating_sub(elapsed));
+                }
+                did_update
+            }
+            None => {
+                if !paused {
+                    Clock::advance_update(frame_time);
+                    self.game.update(frame_time);
+                }
+                !paused
+            }
+        };
+        self.game.post_update(did_update);
     }
 
     fn draw(&mut self) {
         {
             let mut dropped_textures = DROPPED_TEXTURES.lock().unwrap();
-            for texture in dropped_textures.drain(..) {
-                self.context.delete_texture(texture);
+            for (texture, pass) in dropped_textures.drain(..) {
+                match pass {
+                    // Deleting a render pass deletes its color texture too.
+                    Some(pass) => self.context.delete_render_pass(pass),
+                    None => self.context.delete_texture(texture),
+                }
             }
         }
 
         self.game.render(&mut self.context);
+        self.game.post_render(&mut self.context);
         self.context.commit_frame();
+        Clock::advance_frame();
     }
 
     fn quit_requested_event(&mut self) {
@@ -167,7 +538,31 @@ impl<G: Game> EventHandler for Stage<G> {
     }
 
     fn resize_event(&mut self, width: f32, height: f32) {
-        self.game.set_screen_size(width, height);
+        let dpi_scale = miniquad::window::dpi_scale();
+        if dpi_scale != self.dpi_scale {
+            self.dpi_scale = dpi_scale;
+            self.game.dpi_scale_changed(dpi_scale);
+        }
+        resize_game(&mut self.game, width, height);
+    }
+
+    fn window_minimized_event(&mut self) {
+        self.minimized = true;
+        self.game.focus_event(false);
+    }
+    fn window_restored_event(&mut self) {
+        self.minimized = false;
+        self.resumed = true;
+        self.game.focus_event(true);
+    }
+    fn files_dropped_event(&mut self) {
+        let files = (0..miniquad::window::dropped_file_count())
+            .map(|index| DroppedFile {
+                path: miniquad::window::dropped_file_path(index),
+                bytes: miniquad::window::dropped_file_bytes(index),
+            })
+            .collect();
+        self.game.files_dropped(files);
     }
 
     fn mouse_motion_event(&mut self, x: f32, y: f32) {
@@ -180,8 +575,10 @@ impl<G: Game> EventHandler for Stage<G> {
             delta: Vec2::new(dx, dy),
         });
     }
-    fn mouse_wheel_event(&mut self, _x: f32, _y: f32) {
-        // TODO
+    fn mouse_wheel_event(&mut self, x: f32, y: f32) {
+        self.game.handle_event(InputEvent::MouseWheel {
+            delta: Vec2::new(x, y),
+        });
     }
     fn mouse_button_down_event(&mut self, button: MouseButton, _x: f32, _y: f32) {
         if let Ok(button) = button.try_into() {
@@ -200,37 +597,65 @@ impl<G: Game> EventHandler for Stage<G> {
         }
     }
 
-    fn key_down_event(&mut self, keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {
+    fn key_down_event(&mut self, keycode: KeyCode, keymods: KeyMods, repeat: bool) {
         self.game.handle_event(InputEvent::Key {
             key: keycode,
+            mods: keymods.into(),
             pressed: true,
+            repeat,
         });
     }
-    fn key_up_event(&mut self, keycode: KeyCode, _keymods: KeyMods) {
+    fn key_up_event(&mut self, keycode: KeyCode, keymods: KeyMods) {
         self.game.handle_event(InputEvent::Key {
             key: keycode,
+            mods: keymods.into(),
             pressed: false,
+            repeat: false,
         });
     }
-    fn char_event(&mut self, _character: char, _keymods: KeyMods, _repeat: bool) {
-        // TODO
+    fn char_event(&mut self, character: char, keymods: KeyMods, repeat: bool) {
+        self.game.handle_event(InputEvent::Char {
+            character,
+            mods: keymods.into(),
+            repeat,
+        });
     }
 }
 
+fn default_font() -> Font {
+    Font::try_from_slice(include_bytes!("../fonts/OpenSans-Regular.ttf")).unwrap()
+}
+
+// A missing font is replaced by the default font, rather than stopping the game. This keeps the FontIds of the
+// other fonts the same. Returns the fonts and the errors for the ones that couldn't be loaded.
+fn load_fonts(font_files: Vec<&str>) -> (Vec<Font>, Vec<String>) {
+    let mut fonts = Vec::new();
+    let mut errors = Vec::new();
+    for font_file in font_files {
+        match asset::load_font_file("fonts", font_file) {
+            Ok(font) => fonts.push(font),
+            Err(error) => {
+                errors.push(error.to_string());
+                fonts.push(default_font());
+            }
+        }
+    }
+    (fonts, errors)
+}
+
 fn load_stage1<G: GameLoader>() -> asset::Result<(WindowConfig, Vec<Font>)> {
     println!("{}", console::style("Loading game (stage 1)").bold());
 
     #[cfg(debug_assertions)]
     {
-        asset::create_dir("config");
-        let mut lang_dir = asset::base_path();
-        lang_dir.push("lang");
-        if !lang_dir.exists() {
-            println!("Creating directory {}", lang_dir.to_string_lossy());
-            std::fs::create_dir(&lang_dir).expect("could not create lang directory");
-            lang_dir.push("en.yaml");
-            println!("Creating empty file {}", lang_dir.to_string_lossy());
-            std::fs::write(lang_dir, "").expect("could not create lang file");
+        asset::create_dir("config")?;
+        if !asset::get_path("lang", "").exists() {
+            asset::create_dir("lang")?;
+            println!(
+                "Creating empty file {}",
+                asset::get_path("lang", "en.yaml").to_string_lossy()
+            );
+            asset::save_text_file("lang", "en.yaml", "")?;
         }
         WindowConfig::create_default_config_if_missing()?;
         G::create_default_files()?;
@@ -238,27 +663,139 @@ fn load_stage1<G: GameLoader>() -> asset::Result<(WindowConfig, Vec<Font>)> {
 
     let window_config = WindowConfig::load_config()?;
     lang::load_translations()?;
-    let mut fonts = Vec::new();
-    for font_file in G::fonts() {
-        fonts.push(asset::load_font_file("fonts", font_file)?);
+    #[cfg(debug_assertions)]
+    {
+        let keys = G::translation_keys();
+        if !keys.is_empty() {
+            let problems = lang::validate_all_locales(&keys)?;
+            if !problems.is_empty() {
+                nonfatal_error(&format!("Missing translations:\n{}", problems.join("\n")));
+            }
+        }
+    }
+    let (fonts, errors) = load_fonts(G::fonts());
+    if !errors.is_empty() {
+        nonfatal_error(&format!(
+            "Some fonts could not be loaded, and the default font is used instead.\n\n{}",
+            errors.join("\n")
+        ));
     }
     Ok((window_config, fonts))
 }
 
+// Stands in for the game while a LoadQueue is loading, then loads and creates the game and passes everything on to it.
+enum LoadingGame<G: GameLoader> {
+    Loading {
+        renderer: Option<Box<QuadRenderer>>,
+        loading: asset::Loading,
+        screen_size: (f32, f32),
+        dpi_scale: f32,
+    },
+    Running(G::Game),
+}
+
+impl<G: GameLoader> Game for LoadingGame<G> {
+    fn set_screen_size(&mut self, width: f32, height: f32) {
+        match self {
+            LoadingGame::Loading {
+                renderer,
+                screen_size,
+                ..
+            } => {
+                *screen_size = (width, height);
+                if let Some(renderer) = renderer {
+                    renderer.set_screen_size(width, height);
+                }
+            }
+            LoadingGame::Running(game) => game.set_screen_size(width, height),
+        }
+    }
+    fn handle_event(&mut self, event: InputEvent) {
+        if let LoadingGame::Running(game) = self {
+            game.handle_event(event);
+        }
+    }
+    fn quit_requested(&mut self) -> bool {
+        match self {
+            LoadingGame::Loading { .. } => true,
+            LoadingGame::Running(game) => game.quit_requested(),
+        }
+    }
+    fn focus_event(&mut self, focused: bool) {
+        if let LoadingGame::Running(game) = self {
+            game.focus_event(focused);
+        }
+    }
+    fn files_dropped(&mut self, files: Vec<DroppedFile>) {
+        if let LoadingGame::Running(game) = self {
+            game.files_dropped(files);
+        }
+    }
+    fn dpi_scale_changed(&mut self, new_dpi_scale: f32) {
+        match self {
+            LoadingGame::Loading {
+                renderer,
+                dpi_scale,
+                ..
+            } => {
+                *dpi_scale = new_dpi_scale;
+                if let Some(renderer) = renderer {
+                    renderer.set_dpi_scale(new_dpi_scale);
+                }
+            }
+            LoadingGame::Running(game) => game.dpi_scale_changed(new_dpi_scale),
+        }
+    }
+    fn update(&mut self, frame_time: Duration) {
+        if let LoadingGame::Running(game) = self {
+            game.update(frame_time);
+        }
+    }
+    fn post_update(&mut self, did_update: bool) {
+        if let LoadingGame::Running(game) = self {
+            game.post_update(did_update);
+        }
+    }
+    fn render(&mut self, context: &mut RenderingContext) {
+        if let LoadingGame::Loading {
+            renderer,
+            loading,
+            screen_size,
+            dpi_scale,
+        } = self
+        {
+            if !loading.is_finished() {
+                G::render_loading(renderer.as_mut().unwrap(), context, loading.progress());
+                return;
+            }
+            let assets = match load_stage2::<G>(context) {
+                Ok(assets) => assets,
+                Err(error) => loading_failed(error),
+            };
+            let mut game = create_game::<G>(*renderer.take().unwrap(), assets);
+            game.dpi_scale_changed(*dpi_scale);
+            resize_game(&mut game, screen_size.0, screen_size.1);
+            *self = LoadingGame::Running(game);
+        }
+        if let LoadingGame::Running(game) = self {
+            game.render(context);
+        }
+    }
+    fn post_render(&mut self, context: &mut RenderingContext) {
+        if let LoadingGame::Running(game) = self {
+            game.post_render(context);
+        }
+    }
+}
+
 fn load_stage2<G: GameLoader>(context: &mut RenderingContext) -> asset::Result<G::Assets> {
     println!("{}", console::style("Loading game (stage 2)").bold());
     G::load(context)
 }
 
-fn create_game<G: GameLoader>(
-    renderer: QuadRenderer,
-    assets: G::Assets,
-    screen_size: (f32, f32),
-) -> G::Game {
+fn create_game<G: GameLoader>(renderer: QuadRenderer, assets: G::Assets) -> G::Game {
     println!("{}", console::style("Starting game loop").bold());
-    let mut game = G::create_game(renderer, assets);
-    game.set_screen_size(screen_size.0, screen_size.1);
-    game
+    G::create_game(renderer, assets)
 }
 
 fn error_log_path() -> PathBuf {
@@ -278,6 +815,22 @@ fn append_error_log(message: String) {
     }
 }
 
+// Problems that don't need the player's attention, like a setting that isn't supported. They go to error.log too,
+// since players rarely see the console.
+fn log_note(message: &str) {
+    append_error_log(format!("note: {}", message));
+    println!("{}", console::style(format!("Note: {}", message)).yellow());
+}
+
+#[cfg(feature = "native-dialogs")]
+fn show_error_dialog(title: &str, message: &str) {
+    let _ = msgbox::create(title, message, msgbox::IconType::Error);
+}
+#[cfg(not(feature = "native-dialogs"))]
+fn show_error_dialog(title: &str, message: &str) {
+    eprintln!("{}: {}", title, message);
+}
+
 #[track_caller]
 pub fn nonfatal_error(message: &str) {
     append_error_log(format!(
@@ -289,7 +842,7 @@ pub fn nonfatal_error(message: &str) {
         "{}",
         console::style("A nonfatal error occurred. See error.log for details.").red()
     );
-    let _ = msgbox::create("Error", message, msgbox::IconType::Error);
+    show_error_dialog("Error", message);
 }
 
 pub trait ResultExt<T> {
@@ -325,27 +878,99 @@ fn panic_handler(panic_info: &std::panic::PanicInfo) {
     let message = payload
         .strip_prefix("called `Result::unwrap()` on an `Err` value: ")
         .unwrap_or(payload);
-    let _ = msgbox::create("Fatal Error", message, msgbox::IconType::Error);
+    show_error_dialog("Fatal Error", message);
+}
+
+// Asset errors while loading are expected (a missing or malformed file), so they're reported with the file and reason
+// instead of going through the panic handler, and the game exits.
+fn loading_failed(error: asset::AssetError) -> ! {
+    let message = format!("The game could not be loaded.\n\n{}", error);
+    append_error_log(message.clone());
+    println!(
+        "{}",
+        console::style("A fatal error occurred while loading. See error.log for details.").red()
+    );
+    show_error_dialog("Fatal Error", &message);
+    std::process::exit(1);
 }
 
 pub fn run_game<G: GameLoader>(window_title: &str) {
     let _ = std::fs::remove_file(error_log_path());
     std::panic::set_hook(Box::new(panic_handler));
-    let (window_config, fonts) = load_stage1::<G>().unwrap();
-    let config = conf::Conf {
-        window_title: window_title.to_string(),
-        window_width: window_config.width.try_into().unwrap(),
-        window_height: window_config.height.try_into().unwrap(),
-        fullscreen: window_config.fullscreen,
-        window_resizable: false,
-        ..Default::default()
+    let (window_config, fonts) = match load_stage1::<G>() {
+        Ok(loaded) => loaded,
+        Err(error) => loading_failed(error),
     };
-    let screen_size = (window_config.width as f32, window_config.height as f32);
+    let (config, notes) = window_config.to_conf(window_title);
+    for note in notes {
+        log_note(note);
+    }
+    window::FULLSCREEN.store(window_config.fullscreen, Ordering::Relaxed);
     miniquad::start(config, move || {
+        assert!(
+            !CONTEXT_CREATED.swap(true, Ordering::SeqCst),
+            "only one rendering context can be created"
+        );
         let mut context = miniquad::window::new_rendering_backend();
-        let assets = load_stage2::<G>(&mut context).unwrap();
-        let renderer = QuadRenderer::new(&mut context, fonts, true);
-        let game = create_game::<G>(renderer, assets, screen_size);
-        Box::new(Stage::new(game, context, window_config))
+        asset::set_invalid_texture_fallback(G::invalid_texture_fallback());
+        let mut builder = QuadRendererBuilder::new(fonts)
+            .pixel_perfect(true)
+            .dpi_scale(miniquad::window::dpi_scale());
+        for (index, font_file) in G::fonts().into_iter().enumerate() {
+            builder = builder.font_name(font_file, silica::FontId(index));
+        }
+        let mut renderer = builder.build(&mut context);
+        G::prewarm_glyphs(&mut renderer, &mut context);
+        let mut queue = asset::LoadQueue::new();
+        G::queue_assets(&mut queue);
+        if queue.is_empty() {
+            let assets = match load_stage2::<G>(&mut context) {
+                Ok(assets) => assets,
+                Err(error) => loading_failed(error),
+            };
+            let game = create_game::<G>(renderer, assets);
+            Box::new(Stage::new(game, context, window_config))
+        } else {
+            println!(
+                "{}",
+                console::style(format!("Loading {} files in the background", queue.len())).bold()
+            );
+            let game = LoadingGame::<G>::Loading {
+                renderer: Some(Box::new(renderer)),
+                loading: queue.start(),
+                screen_size: (0., 0.),
+                dpi_scale: 1.,
+            };
+            Box::new(Stage::new(game, context, window_config)) as Box<dyn EventHandler>
+        }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_font_falls_back() {
+        let (fonts, errors) = load_fonts(vec!["missing-font.ttf"]);
+        assert_eq!(fonts.len(), 1);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("missing-font.ttf"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn window_config_notes() {
+        let (_, notes) = WindowConfig::default().to_conf("");
+        assert!(notes.is_empty(), "{:?}", notes);
+
+        let config = WindowConfig {
+            fullscreen: true,
+            borderless: true,
+            always_on_top: true,
+            ..Default::default()
+        };
+        let (conf, notes) = config.to_conf("");
+        assert_eq!(notes.len(), 2, "{:?}", notes);
+        assert!(conf.platform.wayland_use_fallback_decorations);
+    }
+}
@@ -0,0 +1,194 @@
+use grist::{impl_add_event_listener, Event};
+use std::time::Duration;
+
+// Fires its elapsed event after a duration, once or repeating. Advance it with the frame time.
+pub struct Timer {
+    duration: Duration,
+    elapsed: Duration,
+    repeat: bool,
+    finished: bool,
+    elapsed_event: Event<()>,
+}
+
+impl Timer {
+    pub fn new(duration: Duration) -> Self {
+        Timer {
+            duration,
+            elapsed: Duration::ZERO,
+            repeat: false,
+            finished: false,
+            elapsed_event: Event::new(),
+        }
+    }
+    pub fn repeating(interval: Duration) -> Self {
+        Timer {
+            repeat: true,
+            ..Self::new(interval)
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+    pub fn remaining(&self) -> Duration {
+        self.duration.saturating_sub(self.elapsed)
+    }
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+    pub fn reset(&mut self) {
+        self.elapsed = Duration::ZERO;
+        self.finished = false;
+    }
+
+    // Returns how many times the timer fired. A repeating timer fires once for each interval that passed, but a zero
+    // interval only fires once per update.
+    pub fn update(&mut self, frame_time: Duration) -> usize {
+        if self.finished {
+            return 0;
+        }
+        self.elapsed += frame_time;
+        let mut fired = 0;
+        while self.elapsed >= self.duration {
+            self.elapsed -= self.duration;
+            self.elapsed_event.emit(&());
+            fired += 1;
+            if !self.repeat {
+                self.finished = true;
+                break;
+            }
+            if self.duration.is_zero() {
+                break;
+            }
+        }
+        fired
+    }
+}
+
+impl_add_event_listener!(
+    Timer,
+    elapsed_event,
+    (),
+    add_elapsed_listener,
+    remove_elapsed_listener
+);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TimerHandle(u64);
+
+// Callbacks scheduled by time. One-shot timers are removed once they fire.
+#[derive(Default)]
+pub struct TimerSet {
+    next_id: u64,
+    timers: Vec<(TimerHandle, Timer)>,
+}
+
+impl TimerSet {
+    pub fn new() -> Self {
+        TimerSet::default()
+    }
+
+    pub fn add(&mut self, timer: Timer) -> TimerHandle {
+        let handle = TimerHandle(self.next_id);
+        self.next_id += 1;
+        self.timers.push((handle, timer));
+        handle
+    }
+    pub fn after<F>(&mut self, duration: Duration, mut f: F) -> TimerHandle
+    where
+        F: FnMut() + 'static,
+    {
+        let mut timer = Timer::new(duration);
+        timer.add_elapsed_listener(move |&()| f());
+        self.add(timer)
+    }
+    pub fn every<F>(&mut self, interval: Duration, mut f: F) -> TimerHandle
+    where
+        F: FnMut() + 'static,
+    {
+        let mut timer = Timer::repeating(interval);
+        timer.add_elapsed_listener(move |&()| f());
+        self.add(timer)
+    }
+    // Returns false if the timer already fired (for one-shot timers) or was cancelled.
+    pub fn cancel(&mut self, handle: TimerHandle) -> bool {
+        let len = self.timers.len();
+        self.timers
+            .retain(|(timer_handle, _)| *timer_handle != handle);
+        self.timers.len() != len
+    }
+    pub fn contains(&self, handle: TimerHandle) -> bool {
+        self.timers
+            .iter()
+            .any(|(timer_handle, _)| *timer_handle == handle)
+    }
+    pub fn len(&self) -> usize {
+        self.timers.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.timers.is_empty()
+    }
+
+    pub fn update(&mut self, frame_time: Duration) {
+        for (_, timer) in self.timers.iter_mut() {
+            timer.update(frame_time);
+        }
+        self.timers.retain(|(_, timer)| !timer.is_finished());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, rc::Rc};
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn one_shot_fires_once() {
+        let mut timer = Timer::new(10 * MS);
+        assert_eq!(timer.update(6 * MS), 0);
+        assert_eq!(timer.remaining(), 4 * MS);
+        assert_eq!(timer.update(6 * MS), 1);
+        assert!(timer.is_finished());
+        assert_eq!(timer.update(20 * MS), 0);
+        timer.reset();
+        assert_eq!(timer.update(10 * MS), 1);
+    }
+
+    #[test]
+    fn repeating_fires_per_interval() {
+        let mut timer = Timer::repeating(10 * MS);
+        let count = Rc::new(Cell::new(0));
+        let counter = count.clone();
+        timer.add_elapsed_listener(move |&()| counter.set(counter.get() + 1));
+        assert_eq!(timer.update(25 * MS), 2);
+        assert_eq!(timer.update(5 * MS), 1);
+        assert_eq!(count.get(), 3);
+        assert!(!timer.is_finished());
+
+        let mut zero = Timer::repeating(Duration::ZERO);
+        assert_eq!(zero.update(5 * MS), 1);
+    }
+
+    #[test]
+    fn timer_set() {
+        let mut timers = TimerSet::new();
+        let count = Rc::new(Cell::new(0));
+        let counter = count.clone();
+        let once = timers.after(10 * MS, move || counter.set(counter.get() + 1));
+        let counter = count.clone();
+        let every = timers.every(4 * MS, move || counter.set(counter.get() + 10));
+        let cancelled = timers.after(MS, || panic!("cancelled timer fired"));
+        assert!(timers.cancel(cancelled));
+
+        timers.update(9 * MS);
+        assert_eq!(count.get(), 20);
+        timers.update(MS);
+        assert_eq!(count.get(), 21);
+        assert!(!timers.contains(once));
+        assert!(!timers.cancel(once));
+        assert!(timers.contains(every));
+        assert_eq!(timers.len(), 1);
+    }
+}
@@ -0,0 +1,114 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::two::SpriteSheet;
+
+type Condition<C> = Box<dyn Fn(&C) -> bool>;
+
+struct AnimationState<C> {
+    animation: String,
+    min_time: f32,
+    on_finish: Option<String>,
+    transitions: Vec<(String, Condition<C>)>,
+}
+
+// Switches the animation of a SpriteSheet automatically. Each state plays an animation, and moves to another state
+// when one of its transition conditions is true. Conditions are checked against the context passed to update.
+pub struct AnimatedSprite<C> {
+    sprite: SpriteSheet,
+    states: HashMap<String, AnimationState<C>>,
+    current_state: String,
+    state_time: f32,
+}
+
+impl<C> AnimatedSprite<C> {
+    pub fn new(sprite: SpriteSheet) -> Self {
+        AnimatedSprite {
+            sprite,
+            states: HashMap::new(),
+            current_state: String::new(),
+            state_time: 0.,
+        }
+    }
+
+    pub fn sprite(&self) -> &SpriteSheet {
+        &self.sprite
+    }
+    pub fn state(&self) -> &str {
+        &self.current_state
+    }
+
+    pub fn add_state(&mut self, state: &str, animation: &str) {
+        self.states.insert(
+            state.to_owned(),
+            AnimationState {
+                animation: animation.to_owned(),
+                min_time: 0.,
+                on_finish: None,
+                transitions: Vec::new(),
+            },
+        );
+    }
+    // Transitions won't be taken until the state has played for at least this long.
+    pub fn set_min_time(&mut self, state: &str, min_time: Duration) {
+        if let Some(state) = self.get_state_mut(state) {
+            state.min_time = min_time.as_secs_f32();
+        }
+    }
+    // Moves to the next state once the animation has played through, for one-shot animations.
+    pub fn set_on_finish(&mut self, state: &str, next: &str) {
+        if let Some(state) = self.get_state_mut(state) {
+            state.on_finish = Some(next.to_owned());
+        }
+    }
+    pub fn add_transition<F>(&mut self, from: &str, to: &str, condition: F)
+    where
+        F: Fn(&C) -> bool + 'static,
+    {
+        if let Some(state) = self.get_state_mut(from) {
+            state.transitions.push((to.to_owned(), Box::new(condition)));
+        }
+    }
+
+    pub fn set_state(&mut self, state: &str) {
+        if let Some(animation_state) = self.states.get(state) {
+            if let Err(error) = self.sprite.set_animation(&animation_state.animation) {
+                eprintln!("{}", error);
+            }
+            self.current_state = state.to_owned();
+            self.state_time = 0.;
+        } else {
+            eprintln!("No animation state called {}", state);
+        }
+    }
+
+    pub fn update(&mut self, frame_time: Duration, context: &C) {
+        self.sprite.animate(frame_time);
+        self.state_time += frame_time.as_secs_f32();
+
+        let mut next = None;
+        if let Some(state) = self.states.get(&self.current_state) {
+            if self.state_time >= state.min_time {
+                next = if self.sprite.animation_loops() > 0 && state.on_finish.is_some() {
+                    state.on_finish.clone()
+                } else {
+                    state
+                        .transitions
+                        .iter()
+                        .find(|(_, condition)| condition(context))
+                        .map(|(to, _)| to.clone())
+                };
+            }
+        }
+        if let Some(next) = next {
+            self.set_state(&next);
+        }
+    }
+
+    fn get_state_mut(&mut self, state: &str) -> Option<&mut AnimationState<C>> {
+        let animation_state = self.states.get_mut(state);
+        if animation_state.is_none() {
+            eprintln!("No animation state called {}", state);
+        }
+        animation_state
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
+use crate::{asset, math::Vec2, two::Rect, RenderingContext, Texture};
+
+// A texture made of named sub-images. The definition file maps each name to its rect in pixels.
+pub struct Atlas {
+    texture: Texture,
+    texture_size: Vec2,
+    rects: HashMap<String, Rect>,
+    reported_missing: Mutex<HashSet<String>>,
+}
+
+impl Atlas {
+    pub fn new(
+        context: &mut RenderingContext,
+        texture: Texture,
+        rects: HashMap<String, Rect>,
+    ) -> Self {
+        let texture_size = context.texture_size(texture.id());
+        Atlas {
+            texture,
+            texture_size: Vec2::new(texture_size.0 as f32, texture_size.1 as f32),
+            rects,
+            reported_missing: Mutex::new(HashSet::new()),
+        }
+    }
+    pub fn load(context: &mut RenderingContext, file: &str) -> asset::Result<Self> {
+        let image_file = format!("{}.png", file);
+        let definition_file = format!("{}.yaml", file);
+        let texture = asset::load_png_file(context, "images", &image_file)?;
+        let rects = asset::load_yaml_file("images", &definition_file)?;
+        Ok(Self::new(context, texture, rects))
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+    pub fn contains(&self, name: &str) -> bool {
+        self.rects.contains_key(name)
+    }
+    pub fn rect(&self, name: &str) -> Option<Rect> {
+        self.rects.get(name).copied()
+    }
+    pub fn uv_rect(&self, name: &str) -> Option<Rect> {
+        self.rect(name).map(|mut rect| {
+            rect.position /= self.texture_size;
+            rect.size /= self.texture_size;
+            rect
+        })
+    }
+
+    // Returns true the first time a missing name is reported, so the error is only shown once.
+    pub(crate) fn report_missing(&self, name: &str) -> bool {
+        self.reported_missing
+            .lock()
+            .unwrap()
+            .insert(name.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TextureHandle;
+    use miniquad::{RawId, TextureId};
+    use std::sync::Arc;
+
+    fn atlas() -> Atlas {
+        // A raw id, since there's no context to make a texture with. It's never drawn.
+        let texture = Texture(Arc::new(TextureHandle(
+            TextureId::from_raw_id(RawId::OpenGl(0)),
+            None,
+        )));
+        Atlas {
+            texture,
+            texture_size: Vec2::new(64., 32.),
+            rects: HashMap::from([("player".to_owned(), Rect::new(16., 8., 16., 16.))]),
+            reported_missing: Mutex::new(HashSet::new()),
+        }
+    }
+
+    #[test]
+    fn uv_rect() {
+        let atlas = atlas();
+        assert!(atlas.contains("player"));
+        assert_eq!(
+            atlas.uv_rect("player"),
+            Some(Rect::new(0.25, 0.25, 0.25, 0.5))
+        );
+    }
+
+    #[test]
+    fn missing_name_reported_once() {
+        let atlas = atlas();
+        assert_eq!(atlas.uv_rect("enemy"), None);
+        assert!(atlas.report_missing("enemy"));
+        assert!(!atlas.report_missing("enemy"));
+        assert!(atlas.report_missing("coin"));
+    }
+}
@@ -0,0 +1,134 @@
+use glam::Vec2;
+
+use crate::two::{Anchor, Rect};
+
+// The camera position is the point in the world at the center of the view.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Camera2D {
+    pub position: Vec2,
+    pub zoom: f32,
+    pub rotation: f32,
+    pub view_size: Vec2,
+}
+
+impl Default for Camera2D {
+    fn default() -> Self {
+        Camera2D {
+            position: Vec2::ZERO,
+            zoom: 1.,
+            rotation: 0.,
+            view_size: Vec2::ONE,
+        }
+    }
+}
+
+impl Camera2D {
+    pub fn new(view_size: Vec2) -> Self {
+        Camera2D {
+            view_size,
+            ..Default::default()
+        }
+    }
+
+    // The area of the world that's visible, ignoring rotation.
+    pub fn view_rect(&self) -> Rect {
+        Rect::from_anchor(self.view_size / self.zoom, Anchor::Center, self.position)
+    }
+
+    // Moves the camera just enough to keep the target inside the dead zone, which is relative to the camera
+    // position, then keeps the view inside the bounds. If the bounds are smaller than the view it's centered on them.
+    pub fn follow(&mut self, target: Vec2, bounds: Rect, deadzone: Rect) {
+        let deadzone_min = self.position + deadzone.position;
+        let deadzone_max = deadzone_min + deadzone.size;
+        let half_view = self.view_size / (2. * self.zoom);
+        let follow_axis = |position: &mut f32, target, deadzone_min, deadzone_max| {
+            if target < deadzone_min {
+                *position += target - deadzone_min;
+            } else if target > deadzone_max {
+                *position += target - deadzone_max;
+            }
+        };
+        let clamp_axis = |position: &mut f32, bounds_min: f32, bounds_size: f32, half_view: f32| {
+            let min = bounds_min + half_view;
+            let max = bounds_min + bounds_size - half_view;
+            if min > max {
+                *position = bounds_min + bounds_size / 2.;
+            } else {
+                *position = position.clamp(min, max);
+            }
+        };
+        follow_axis(
+            &mut self.position.x,
+            target.x,
+            deadzone_min.x,
+            deadzone_max.x,
+        );
+        follow_axis(
+            &mut self.position.y,
+            target.y,
+            deadzone_min.y,
+            deadzone_max.y,
+        );
+        clamp_axis(
+            &mut self.position.x,
+            bounds.x(),
+            bounds.width(),
+            half_view.x,
+        );
+        clamp_axis(
+            &mut self.position.y,
+            bounds.y(),
+            bounds.height(),
+            half_view.y,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDS: Rect = Rect {
+        position: Vec2::ZERO,
+        size: Vec2::splat(100.),
+    };
+
+    fn camera() -> Camera2D {
+        Camera2D {
+            position: Vec2::splat(50.),
+            ..Camera2D::new(Vec2::splat(10.))
+        }
+    }
+    fn deadzone() -> Rect {
+        Rect::new(-2., -2., 4., 4.)
+    }
+
+    #[test]
+    fn follow_within_deadzone() {
+        let mut camera = camera();
+        camera.follow(Vec2::new(51., 49.), BOUNDS, deadzone());
+        assert_eq!(camera.position, Vec2::splat(50.));
+        camera.follow(Vec2::new(55., 44.), BOUNDS, deadzone());
+        assert_eq!(camera.position, Vec2::new(53., 46.));
+    }
+
+    #[test]
+    fn clamp_to_level_edges() {
+        let mut camera = camera();
+        camera.follow(Vec2::ZERO, BOUNDS, deadzone());
+        assert_eq!(camera.position, Vec2::splat(5.));
+        camera.follow(Vec2::splat(200.), BOUNDS, deadzone());
+        assert_eq!(camera.position, Vec2::splat(95.));
+
+        camera.zoom = 2.;
+        camera.follow(Vec2::ZERO, BOUNDS, deadzone());
+        assert_eq!(camera.position, Vec2::splat(2.5));
+    }
+
+    #[test]
+    fn center_in_small_level() {
+        let mut camera = camera();
+        camera.follow(Vec2::ZERO, Rect::new(0., 0., 6., 100.), deadzone());
+        assert_eq!(camera.position, Vec2::new(3., 5.));
+    }
+}
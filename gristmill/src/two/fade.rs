@@ -0,0 +1,128 @@
+use grist::{impl_add_event_listener, Event};
+use palette::LinSrgba;
+use std::time::Duration;
+
+use crate::two::{QuadRenderer, Rect, RenderQuad, Renderable};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FadeState {
+    Idle,
+    Out,
+    Hold,
+    In,
+}
+
+// Fades the screen out to a color and back in. The midpoint event fires when the screen is fully covered, which is
+// when to switch scenes. Queue it after everything else so it's drawn on top.
+pub struct ScreenFade {
+    color: LinSrgba,
+    out_duration: Duration,
+    hold_duration: Duration,
+    in_duration: Duration,
+    state: FadeState,
+    state_time: Duration,
+    midpoint: Event<()>,
+}
+
+impl ScreenFade {
+    pub fn new(duration: Duration) -> Self {
+        ScreenFade {
+            color: LinSrgba::new(0., 0., 0., 1.),
+            out_duration: duration,
+            hold_duration: Duration::ZERO,
+            in_duration: duration,
+            state: FadeState::Idle,
+            state_time: Duration::ZERO,
+            midpoint: Event::new(),
+        }
+    }
+
+    pub fn set_color(&mut self, color: LinSrgba) {
+        self.color = color;
+    }
+    pub fn set_durations(&mut self, fade_out: Duration, hold: Duration, fade_in: Duration) {
+        self.out_duration = fade_out;
+        self.hold_duration = hold;
+        self.in_duration = fade_in;
+    }
+
+    pub fn state(&self) -> FadeState {
+        self.state
+    }
+    pub fn is_active(&self) -> bool {
+        self.state != FadeState::Idle
+    }
+    pub fn alpha(&self) -> f32 {
+        let progress = |duration: Duration| {
+            if duration.is_zero() {
+                1.
+            } else {
+                (self.state_time.as_secs_f32() / duration.as_secs_f32()).min(1.)
+            }
+        };
+        match self.state {
+            FadeState::Idle => 0.,
+            FadeState::Out => progress(self.out_duration),
+            FadeState::Hold => 1.,
+            FadeState::In => 1. - progress(self.in_duration),
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.state = FadeState::Out;
+        self.state_time = Duration::ZERO;
+    }
+    pub fn update(&mut self, frame_time: Duration) {
+        if self.state == FadeState::Idle {
+            return;
+        }
+        self.state_time += frame_time;
+        loop {
+            let duration = match self.state {
+                FadeState::Idle => return,
+                FadeState::Out => self.out_duration,
+                FadeState::Hold => self.hold_duration,
+                FadeState::In => self.in_duration,
+            };
+            if self.state_time < duration {
+                return;
+            }
+            self.state_time -= duration;
+            self.state = match self.state {
+                FadeState::Out => {
+                    self.midpoint.emit(&());
+                    FadeState::Hold
+                }
+                FadeState::Hold => FadeState::In,
+                _ => FadeState::Idle,
+            };
+        }
+    }
+}
+
+impl_add_event_listener!(
+    ScreenFade,
+    midpoint,
+    (),
+    add_midpoint_listener,
+    remove_midpoint_listener
+);
+
+impl Renderable for ScreenFade {
+    fn render(&self, renderer: &mut QuadRenderer) {
+        let alpha = self.alpha();
+        if alpha <= 0. {
+            return;
+        }
+        let mut color = self.color;
+        color.alpha *= alpha;
+        let size = renderer.screen_size() / (renderer.scale() * renderer.dpi_scale());
+        renderer.queue(RenderQuad {
+            color,
+            // Grown so rounding in pixel perfect mode can't leave a gap at the edges.
+            rect: Rect::from_size(size).grow(1.),
+            scroll: false,
+            ..Default::default()
+        });
+    }
+}
@@ -1,14 +1,23 @@
+mod animated;
+mod atlas;
+mod camera;
+mod fade;
 mod quad;
 mod sprite;
 
 use glam::{IVec2, Vec2};
 use serde::{Deserialize, Serialize};
 
+pub use animated::*;
+pub use atlas::*;
+pub use camera::*;
+pub use fade::*;
 pub use quad::*;
 pub use sprite::*;
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum Anchor {
+    #[default]
     TopLeft,
     TopCenter,
     TopRight,
@@ -132,6 +141,24 @@ impl Rect {
             && position.y >= self.position.y
             && position.y < self.position.y + self.size.y
     }
+    pub fn intersects(&self, other: Rect) -> bool {
+        self.position.x < other.position.x + other.size.x
+            && other.position.x < self.position.x + self.size.x
+            && self.position.y < other.position.y + other.size.y
+            && other.position.y < self.position.y + self.size.y
+    }
+    // The overlapping part of the two rects, or None if they don't overlap.
+    pub fn intersection(&self, other: Rect) -> Option<Rect> {
+        if !self.intersects(other) {
+            return None;
+        }
+        let start = self.position.max(other.position);
+        let end = (self.position + self.size).min(other.position + other.size);
+        Some(Rect {
+            position: start,
+            size: end - start,
+        })
+    }
 
     pub fn grow(mut self, amount: f32) -> Rect {
         self.position.x -= amount;
@@ -284,3 +311,14 @@ impl EdgeRect {
         }
     }
 }
+// For GUI insets, like GuiRenderer::draw_nine_slice.
+impl From<EdgeRect> for silica::taffy::Rect<f32> {
+    fn from(rect: EdgeRect) -> Self {
+        silica::taffy::Rect {
+            left: rect.left as f32,
+            right: rect.right as f32,
+            top: rect.top as f32,
+            bottom: rect.bottom as f32,
+        }
+    }
+}
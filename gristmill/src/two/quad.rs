@@ -1,16 +1,21 @@
 use glyph_brush::{
     ab_glyph::{Font, FontArc, PxScale},
-    BrushAction, BrushError, GlyphBrushBuilder, GlyphCruncher, Section,
+    BrushAction, BrushError, GlyphBrushBuilder, GlyphCruncher, Section, Text,
 };
 use grist::WeakObj;
 use miniquad::*;
 use palette::LinSrgba;
-use silica::taffy::{prelude::*, Point};
+use silica::{
+    taffy::{prelude::*, Point},
+    FontId, Renderer, TextMeasurer,
+};
+use std::{collections::HashMap, ops::RangeInclusive};
 
 use crate::{
-    math::{BVec2, Vec2},
-    two::Rect,
-    RenderingContext, Texture,
+    math::{BVec2, Mat4, Vec2, Vec3, Vec4},
+    nonfatal_error,
+    two::{Atlas, Camera2D, Rect},
+    Clock, RenderTarget, RenderingContext, Texture,
 };
 
 #[derive(Clone)]
@@ -19,12 +24,153 @@ struct Quad {
     rect: [f32; 4],
     uv: [f32; 4],
     color: [f32; 4],
+    data: [f32; 4],
+    // 1 for world quads, which the shader moves by the view matrix.
+    world: f32,
 }
 
 type GlyphBrush = glyph_brush::GlyphBrush<(Quad, usize)>;
 
+// Where glyph_brush puts the glyph images it rasterizes.
+trait GlyphTexture {
+    fn resize(&mut self, size: (u32, u32));
+    fn update(&mut self, rect: glyph_brush::Rectangle<u32>, data: &[u8]);
+}
+
+struct ContextGlyphTexture<'a> {
+    context: &'a mut RenderingContext,
+    texture: &'a mut TextureId,
+}
+
+impl GlyphTexture for ContextGlyphTexture<'_> {
+    fn resize(&mut self, size: (u32, u32)) {
+        *self.texture = QuadRenderer::create_glyph_texture(self.context, size);
+    }
+    fn update(&mut self, rect: glyph_brush::Rectangle<u32>, data: &[u8]) {
+        self.context.texture_update_part(
+            *self.texture,
+            rect.min[0] as i32,
+            rect.min[1] as i32,
+            rect.width() as i32,
+            rect.height() as i32,
+            data,
+        );
+    }
+}
+
+// The text queued for a frame, and the glyphs glyph_brush laid out for it sorted into glyph layers.
+struct GlyphQueue {
+    brush: GlyphBrush,
+    // Glyphs as glyph_brush last laid them out, with the index into sections of the section they're from.
+    vertices: Vec<(Quad, usize)>,
+    instances: Vec<Vec<Quad>>,
+    // The glyph layer and clip rect of each section queued this frame.
+    sections: Vec<(usize, Option<Rect>)>,
+}
+
+impl GlyphQueue {
+    fn new(fonts: Vec<FontArc>) -> Self {
+        GlyphQueue {
+            brush: GlyphBrushBuilder::using_fonts(fonts).build(),
+            vertices: Vec::new(),
+            instances: Vec::new(),
+            sections: Vec::new(),
+        }
+    }
+
+    fn queue(&mut self, mut section: Section, layer: usize, clip: Option<Rect>) {
+        // The section index goes through glyph_brush in z, to find the layer and clip of each glyph.
+        let index = self.sections.len();
+        self.sections.push((layer, clip));
+        for text in section.text.iter_mut() {
+            text.extra.z = index as f32;
+        }
+        self.brush.queue(section);
+    }
+
+    fn process(&mut self, layers: usize, texture: &mut impl GlyphTexture) {
+        let mut brush_action;
+        loop {
+            brush_action = self.brush.process_queued(
+                |rect, tex_data| texture.update(rect, tex_data),
+                |glyph_vertex| {
+                    let (pos, uv) = clip_glyph(
+                        glyph_vertex.pixel_coords,
+                        glyph_vertex.tex_coords,
+                        glyph_vertex.bounds,
+                    );
+                    let pos: Rect = pos.into();
+                    let uv: Rect = uv.into();
+                    let color = glyph_vertex.extra.color;
+                    (
+                        Quad {
+                            rect: pos.into(),
+                            uv: uv.into(),
+                            color,
+                            data: [0.; 4],
+                            world: 0.,
+                        },
+                        glyph_vertex.extra.z as usize,
+                    )
+                },
+            );
+
+            // If the cache texture is too small to fit all the glyphs, resize and try again
+            match brush_action {
+                Ok(_) => break,
+                Err(BrushError::TextureTooSmall { suggested, .. }) => {
+                    println!("Resizing glyph texture to {}x{}", suggested.0, suggested.1);
+                    texture.resize(suggested);
+                    self.brush.resize_texture(suggested.0, suggested.1);
+                }
+            }
+        }
+
+        // If the text has changed from what was last drawn, store new vertices
+        if let BrushAction::Draw(vertices) = brush_action.unwrap() {
+            self.vertices = vertices;
+        }
+        // The layers and clips can change without the text changing, so glyphs are sorted into layers every frame.
+        self.instances.clear();
+        self.instances.resize(layers, Vec::new());
+        for (quad, section) in self.vertices.iter() {
+            let (layer, clip) = self.sections[*section];
+            match clip {
+                None => self.instances[layer].push(quad.clone()),
+                Some(clip) => {
+                    if let Some((rect, uv)) = clip_quad(quad.rect.into(), quad.uv, clip) {
+                        self.instances[layer].push(Quad {
+                            rect: rect.into(),
+                            uv,
+                            ..quad.clone()
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    // Rasterizes the section's glyphs into the cache without drawing them. Processing takes everything queued, so
+    // text already queued this frame would be lost.
+    fn prewarm(&mut self, section: Section, texture: &mut impl GlyphTexture) {
+        assert!(
+            self.sections.is_empty(),
+            "glyphs can't be prewarmed after text is queued for the frame"
+        );
+        self.queue(section, 0, None);
+        self.process(1, texture);
+        self.vertices.clear();
+        self.instances.clear();
+        self.end_frame();
+    }
+
+    fn end_frame(&mut self) {
+        self.sections.clear();
+    }
+}
+
 mod shader {
-    use crate::math::Vec2;
+    use crate::{math::Vec2, RenderingContext};
     use miniquad::*;
 
     pub const VERTEX: &str = r#"#version 100
@@ -35,17 +181,30 @@ mod shader {
     attribute vec4 inst_rect;
     attribute vec4 inst_uv;
     attribute vec4 inst_color;
+    attribute vec4 inst_data;
+    attribute float inst_world;
 
     uniform vec2 screen_size;
+    uniform float y_direction;
+    uniform float pixel_perfect;
+    uniform mat4 view;
 
     varying lowp vec2 texcoord;
     varying lowp vec4 color;
+    varying mediump vec4 data;
 
     void main() {
         vec2 pos = inst_rect.xy + (vert_pos * inst_rect.zw);
-        gl_Position = vec4((pos / screen_size * 2.0 - 1.0) * FLIP_Y, 0.0, 1.0);
+        if (inst_world > 0.5) {
+            pos = (view * vec4(pos, 0.0, 1.0)).xy;
+            if (pixel_perfect > 0.5) {
+                pos = floor(pos + 0.5);
+            }
+        }
+        gl_Position = vec4((pos / screen_size * 2.0 - 1.0) * FLIP_Y * vec2(1.0, y_direction), 0.0, 1.0);
         texcoord = inst_uv.xy + (vert_pos * inst_uv.zw);
         color = inst_color;
+        data = inst_data;
     }"#;
 
     pub const FRAGMENT: &str = r#"#version 100
@@ -67,38 +226,150 @@ mod shader {
         ShaderMeta {
             images: vec!["tex".to_string(), "mask".to_string()],
             uniforms: UniformBlockLayout {
-                uniforms: vec![UniformDesc::new("screen_size", UniformType::Float2)],
+                uniforms: vec![
+                    UniformDesc::new("screen_size", UniformType::Float2),
+                    UniformDesc::new("time", UniformType::Float1),
+                    UniformDesc::new("y_direction", UniformType::Float1),
+                    UniformDesc::new("pixel_perfect", UniformType::Float1),
+                    UniformDesc::new("view", UniformType::Mat4),
+                ],
             },
         }
     }
 
-    pub fn attributes() -> [VertexAttribute; 4] {
+    pub fn attributes() -> [VertexAttribute; 6] {
         [
             VertexAttribute::with_buffer("vert_pos", VertexFormat::Float2, 0),
             VertexAttribute::with_buffer("inst_rect", VertexFormat::Float4, 1),
             VertexAttribute::with_buffer("inst_uv", VertexFormat::Float4, 1),
             VertexAttribute::with_buffer("inst_color", VertexFormat::Float4, 1),
+            VertexAttribute::with_buffer("inst_data", VertexFormat::Float4, 1),
+            VertexAttribute::with_buffer("inst_world", VertexFormat::Float1, 1),
         ]
     }
 
+    pub fn new_pipeline(
+        context: &mut RenderingContext,
+        fragment: &str,
+    ) -> Result<Pipeline, ShaderError> {
+        let shader = context.new_shader(
+            ShaderSource::Glsl {
+                vertex: VERTEX,
+                fragment,
+            },
+            meta(),
+        )?;
+        Ok(context.new_pipeline(
+            &[
+                BufferLayout::default(),
+                BufferLayout {
+                    step_func: VertexStep::PerInstance,
+                    ..Default::default()
+                },
+            ],
+            &attributes(),
+            shader,
+            PipelineParams {
+                color_blend: Some(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+        ))
+    }
+
+    // Must match the order of the uniforms in meta. Shaders that don't declare a uniform just don't get it.
     #[repr(C)]
     pub struct Uniforms {
         pub screen_size: Vec2,
+        pub time: f32,
+        // -1 when drawing into a render target, whose rows are stored bottom up.
+        pub y_direction: f32,
+        pub pixel_perfect: f32,
+        // Column major, from QuadRenderer::view_matrix.
+        pub view: [f32; 16],
     }
 }
 
+// A renderable that's a single quad returns its texture and rect from quad, and queue_all builds the RenderQuad using
+// color, flip and scroll. Anything else queues what it needs in render, which is called after the quad.
 pub trait Renderable {
-    fn render(&self, renderer: &mut QuadRenderer);
+    fn quad(&self) -> Option<(Option<&Texture>, Rect)> {
+        None
+    }
+    fn render(&self, _renderer: &mut QuadRenderer) {}
+    // Lower keys are drawn first, when the RenderList is sorted. E.g. return the Y position of the bottom of the
+    // sprite for a top-down game.
+    fn sort_key(&self) -> f32 {
+        0.
+    }
+
+    // How the quad is drawn.
+    fn color(&self) -> LinSrgba {
+        LinSrgba::new(1., 1., 1., 1.)
+    }
+    fn flip(&self) -> BVec2 {
+        BVec2::FALSE
+    }
+    fn scroll(&self) -> bool {
+        true
+    }
 }
 
-pub struct RenderList(Vec<WeakObj<dyn Renderable>>);
+#[derive(Default)]
+pub struct RenderList {
+    renderables: Vec<WeakObj<dyn Renderable>>,
+    sorted: bool,
+    needs_sort: bool,
+}
 
 impl RenderList {
     pub fn new() -> Self {
-        RenderList(Vec::new())
+        RenderList::default()
+    }
+    // A sorted list orders its renderables by sort_key (keeping the order they were added for equal keys). Sorting
+    // happens when a renderable is added, or after calling mark_unsorted, e.g. when something moved.
+    pub fn new_sorted() -> Self {
+        RenderList {
+            sorted: true,
+            ..Default::default()
+        }
     }
     pub fn add(&mut self, renderable: WeakObj<dyn Renderable>) {
-        self.0.push(renderable);
+        self.renderables.push(renderable);
+        self.needs_sort = self.sorted;
+    }
+    // Removes the renderable while it's still alive, keeping the order of the others. Returns false if it wasn't in
+    // the list.
+    pub fn remove(&mut self, renderable: &WeakObj<dyn Renderable>) -> bool {
+        let len = self.renderables.len();
+        self.renderables.retain(|other| other != renderable);
+        self.renderables.len() != len
+    }
+    pub fn clear(&mut self) {
+        self.renderables.clear();
+        self.needs_sort = false;
+    }
+    pub fn mark_unsorted(&mut self) {
+        self.needs_sort = self.sorted;
+    }
+    pub fn sort(&mut self) {
+        let mut keyed: Vec<(f32, WeakObj<dyn Renderable>)> = self
+            .renderables
+            .drain(..)
+            .filter_map(|renderable| {
+                let key = renderable.try_upgrade()?.get().sort_key();
+                Some((key, renderable))
+            })
+            .collect();
+        keyed.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        self.renderables = keyed
+            .into_iter()
+            .map(|(_, renderable)| renderable)
+            .collect();
+        self.needs_sort = false;
     }
 }
 
@@ -109,6 +380,11 @@ pub struct RenderQuad<'a> {
     pub uv_rect: Rect,
     pub flip: BVec2,
     pub scroll: bool,
+    pub pipeline: Option<CustomPipeline>,
+    pub data: Vec4,
+    // Quads are drawn in order of layer, then in the order they were queued. All text (other than debug text) is in
+    // layer 0, so quads in a negative layer are always drawn below text and quads in a positive layer above it.
+    pub layer: i32,
 }
 
 impl<'a> Default for RenderQuad<'a> {
@@ -121,13 +397,21 @@ impl<'a> Default for RenderQuad<'a> {
             uv_rect: Rect::ONE,
             flip: BVec2::FALSE,
             scroll: true,
+            pipeline: None,
+            data: Vec4::ZERO,
+            layer: 0,
         }
     }
 }
 
+// All text in a glyph layer is drawn with one draw call, after the quads queued before the layer's last text. Text
+// joins the current layer unless a quad has since been queued on top of some of the layer's text, which would then
+// be drawn over the quad. So a screen of buttons (each a background quad and a label) takes two draw calls, instead
+// of two per button.
 struct GlyphLayer {
     layer: usize,
     combine: bool,
+    bounds: Vec<Rect>,
 }
 
 impl GlyphLayer {
@@ -135,70 +419,403 @@ impl GlyphLayer {
         GlyphLayer {
             layer: 0,
             combine: true,
+            bounds: Vec::new(),
         }
     }
     fn next(&mut self) -> usize {
         if !self.combine {
             self.layer += 1;
             self.combine = true;
+            self.bounds.clear();
         }
         self.layer
     }
-    fn finish(&mut self) {
+    fn add_text(&mut self, bounds: Rect) {
+        self.bounds.push(bounds);
+    }
+    fn add_quad(&mut self, rect: Rect) {
+        if self.bounds.iter().any(|bounds| bounds.intersects(rect)) {
+            self.combine = false;
+        }
+    }
+    // Puts the next text in a new layer, e.g. for text drawn in another quad layer.
+    fn split(&mut self) {
         self.combine = false;
     }
     fn reset(&mut self) {
         self.combine = true;
         self.layer = 0;
+        self.bounds.clear();
     }
 }
 
+// Counts for a single frame. queued is every quad passed to queue, including the culled ones, while quads and
+// text_glyphs are what was actually drawn. Each instance range is one draw call; quads with the same texture are
+// merged into one range where the draw order allows it. texture_switches counts draw calls that bound different
+// textures than the draw call before them.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct RenderStats {
+    pub queued: usize,
+    pub culled: usize,
+    pub quads: usize,
+    pub text_glyphs: usize,
+    pub draw_calls: usize,
+    pub texture_switches: usize,
+}
+
+// A pipeline made by QuadRenderer::add_pipeline.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CustomPipeline(usize);
+
+// What needs to be bound to draw a range of instances. Pipeline 0 is the default.
+#[derive(Clone, Copy, PartialEq)]
+struct Batch {
+    texture: TextureId,
+    pipeline: usize,
+    layer: i32,
+}
+
+// Shapes queued with the debug_ methods, drawn on top of everything at the end of the frame.
+enum DebugShape {
+    Rect(Rect, LinSrgba),
+    Line(Vec2, Vec2, LinSrgba),
+    Text(Vec2, String),
+}
+
+// Text is a glyph layer, and the quad layer it's drawn in.
 enum InstanceRange {
-    Instances(TextureId, std::ops::Range<usize>),
-    Text(usize),
+    Instances(Batch, std::ops::Range<usize>),
+    Text(usize, i32),
 }
 
-pub struct QuadRenderer {
-    pixel_perfect: bool,
+impl InstanceRange {
+    fn layer(&self) -> i32 {
+        match self {
+            InstanceRange::Instances(batch, _) => batch.layer,
+            InstanceRange::Text(_, layer) => *layer,
+        }
+    }
+    // The texture and the mask it's drawn with.
+    fn images(&self, white_pixel: TextureId, glyph_texture: TextureId) -> [TextureId; 2] {
+        match self {
+            InstanceRange::Instances(batch, _) => [batch.texture, white_pixel],
+            InstanceRange::Text(..) => [white_pixel, glyph_texture],
+        }
+    }
+}
+
+// The quads and text queued for a frame, in instance ranges that each take one draw call.
+struct QuadQueue {
     instances: Vec<Quad>,
     instance_ranges: Vec<InstanceRange>,
-    screen_size: Vec2,
-    scroll_offset: Vec2,
-    scale: f32,
-    pipeline: Pipeline,
-    vertex_buffer: BufferId,
-    instance_buffer: BufferId,
-    index_buffer: BufferId,
-    white_pixel: Texture,
-    glyph_brush: GlyphBrush,
-    glyph_texture: TextureId,
     glyph_layer: GlyphLayer,
-    glyph_instances: Vec<Vec<Quad>>,
+    texture_batch_start: Option<usize>,
 }
 
-impl QuadRenderer {
-    fn create_glyph_texture(
-        context: &mut RenderingContext,
-        (width, height): (u32, u32),
-    ) -> TextureId {
-        context.new_texture(
-            TextureAccess::Static,
-            TextureSource::Empty,
-            TextureParams {
-                kind: TextureKind::Texture2D,
-                format: TextureFormat::Alpha,
-                wrap: TextureWrap::Clamp,
-                min_filter: FilterMode::Linear,
-                mag_filter: FilterMode::Linear,
-                mipmap_filter: MipmapFilterMode::None,
-                width,
-                height,
-                allocate_mipmaps: false,
-            },
-        )
+impl QuadQueue {
+    fn new() -> Self {
+        QuadQueue {
+            instances: Vec::new(),
+            instance_ranges: Vec::new(),
+            glyph_layer: GlyphLayer::new(),
+            texture_batch_start: None,
+        }
     }
 
-    pub fn new(context: &mut RenderingContext, fonts: Vec<FontArc>, pixel_perfect: bool) -> Self {
+    // Takes the quad in its final form, and the screen pixels it covers.
+    fn push(&mut self, batch: Batch, quad: Quad, screen_rect: Rect) {
+        // Quads in other layers are drawn entirely below or above the text, so they can't split a glyph layer.
+        if batch.layer == 0 {
+            self.glyph_layer.add_quad(screen_rect);
+        }
+        self.instances.push(quad);
+        let end = self.instances.len();
+        let mut appended = false;
+        let batch_started = self.texture_batch_start == Some(self.instance_ranges.len());
+        if let (false, Some(InstanceRange::Instances(instance_batch, range))) =
+            (batch_started, self.instance_ranges.last_mut())
+        {
+            if *instance_batch == batch {
+                range.end = end;
+                appended = true;
+            }
+        }
+        if !appended {
+            let start = end - 1;
+            self.instance_ranges
+                .push(InstanceRange::Instances(batch, start..end));
+        }
+    }
+    // Takes the screen bounds of text about to be queued (None if it has no glyphs), and returns the glyph layer to
+    // queue it in.
+    fn push_text(&mut self, bounds: Option<Rect>, layer: i32) -> usize {
+        let glyph_layer = self.glyph_layer.next();
+        if let Some(bounds) = bounds {
+            self.glyph_layer.add_text(bounds);
+        }
+        if !matches!(self.instance_ranges.last(), Some(InstanceRange::Text(last, _)) if *last == glyph_layer)
+        {
+            // Move the layer's draw after the quads queued since its previous text.
+            if let Some(index) = self.instance_ranges.iter().rposition(
+                |range| matches!(range, InstanceRange::Text(text, _) if *text == glyph_layer),
+            ) {
+                self.remove_instance_range(index);
+                self.merge_instance_ranges(index);
+            }
+            self.instance_ranges
+                .push(InstanceRange::Text(glyph_layer, layer));
+        }
+        glyph_layer
+    }
+    fn glyph_layers(&self) -> usize {
+        self.glyph_layer.layer + 1
+    }
+
+    // Joins the instance range at index with the one before it, if they're contiguous and use the same texture and
+    // pipeline.
+    fn merge_instance_ranges(&mut self, index: usize) {
+        if index == 0
+            || index >= self.instance_ranges.len()
+            || self.texture_batch_start == Some(index)
+        {
+            return;
+        }
+        if let (
+            InstanceRange::Instances(previous_batch, previous),
+            InstanceRange::Instances(batch, range),
+        ) = (
+            &self.instance_ranges[index - 1],
+            &self.instance_ranges[index],
+        ) {
+            if previous_batch == batch && previous.end == range.start {
+                let merged = previous.start..range.end;
+                self.instance_ranges[index - 1] = InstanceRange::Instances(*batch, merged);
+                self.remove_instance_range(index);
+            }
+        }
+    }
+    // Keeps texture_batch_start pointing at the same range when an earlier one is removed.
+    fn remove_instance_range(&mut self, index: usize) {
+        self.instance_ranges.remove(index);
+        if let Some(start) = &mut self.texture_batch_start {
+            if *start > index {
+                *start -= 1;
+            }
+        }
+    }
+
+    fn begin_texture_batch(&mut self) {
+        self.texture_batch_start = Some(self.instance_ranges.len());
+    }
+    // False if no batch was started.
+    fn end_texture_batch(&mut self) -> bool {
+        if let Some(start) = self.texture_batch_start.take() {
+            let batch: Vec<InstanceRange> = self.instance_ranges.drain(start..).collect();
+            let mut run = Vec::new();
+            for range in batch {
+                match range {
+                    InstanceRange::Instances(batch, range) => run.push((batch, range)),
+                    InstanceRange::Text(glyph_layer, layer) => {
+                        self.push_texture_run(std::mem::take(&mut run));
+                        self.instance_ranges
+                            .push(InstanceRange::Text(glyph_layer, layer));
+                    }
+                }
+            }
+            self.push_texture_run(run);
+            true
+        } else {
+            false
+        }
+    }
+    fn push_texture_run(&mut self, run: Vec<(Batch, std::ops::Range<usize>)>) {
+        if run.is_empty() {
+            return;
+        }
+        let start = run[0].1.start;
+        let end = run[run.len() - 1].1.end;
+        let mut batches: Vec<Batch> = Vec::new();
+        for (batch, _) in run.iter() {
+            if !batches.contains(batch) {
+                batches.push(*batch);
+            }
+        }
+        let mut sorted = Vec::with_capacity(end - start);
+        for batch in batches {
+            let batch_start = start + sorted.len();
+            for (_, range) in run.iter().filter(|(b, _)| *b == batch) {
+                sorted.extend_from_slice(&self.instances[range.clone()]);
+            }
+            self.instance_ranges.push(InstanceRange::Instances(
+                batch,
+                batch_start..start + sorted.len(),
+            ));
+        }
+        self.instances.splice(start..end, sorted);
+    }
+
+    // Reorders the instance ranges by layer, keeping the queued order within a layer. Ranges that end up next to each
+    // other with the same texture are merged, so each layer batches as well as it would on its own.
+    fn sort_layers(&mut self) {
+        if self.instance_ranges.iter().all(|range| range.layer() == 0) {
+            return;
+        }
+        self.instance_ranges.sort_by_key(InstanceRange::layer);
+        let mut instances = Vec::with_capacity(self.instances.len());
+        let mut instance_ranges: Vec<InstanceRange> =
+            Vec::with_capacity(self.instance_ranges.len());
+        for instance_range in self.instance_ranges.drain(..) {
+            match instance_range {
+                InstanceRange::Instances(batch, range) => {
+                    let start = instances.len();
+                    instances.extend_from_slice(&self.instances[range]);
+                    if let Some(InstanceRange::Instances(previous_batch, previous)) =
+                        instance_ranges.last_mut()
+                    {
+                        if *previous_batch == batch {
+                            previous.end = instances.len();
+                            continue;
+                        }
+                    }
+                    instance_ranges.push(InstanceRange::Instances(batch, start..instances.len()));
+                }
+                InstanceRange::Text(glyph_layer, layer) => {
+                    instance_ranges.push(InstanceRange::Text(glyph_layer, layer))
+                }
+            }
+        }
+        self.instances = instances;
+        self.instance_ranges = instance_ranges;
+    }
+    // What drawing the instance ranges takes, given the glyphs in each glyph layer. queued and culled are left at 0.
+    fn draw_stats(
+        &self,
+        white_pixel: TextureId,
+        glyph_texture: TextureId,
+        glyphs: &[Vec<Quad>],
+    ) -> RenderStats {
+        let mut stats = RenderStats::default();
+        let mut current_images = None;
+        for instance_range in self.instance_ranges.iter() {
+            match instance_range {
+                InstanceRange::Instances(_, range) => stats.quads += range.len(),
+                InstanceRange::Text(layer, _) => stats.text_glyphs += glyphs[*layer].len(),
+            }
+            let images = instance_range.images(white_pixel, glyph_texture);
+            if current_images.is_some_and(|current| current != images) {
+                stats.texture_switches += 1;
+            }
+            current_images = Some(images);
+            stats.draw_calls += 1;
+        }
+        stats
+    }
+
+    fn clear(&mut self) {
+        self.instances.clear();
+        self.instance_ranges.clear();
+        self.glyph_layer.reset();
+    }
+}
+
+// Cuts off the parts of a glyph outside of its section's bounds, so text never draws outside of them (e.g. outside
+// of a GUI node). The texture coordinates are cut by the same fraction, so a partly clipped glyph isn't stretched.
+fn clip_glyph(
+    mut pixel_coords: glyph_brush::ab_glyph::Rect,
+    mut tex_coords: glyph_brush::ab_glyph::Rect,
+    bounds: glyph_brush::ab_glyph::Rect,
+) -> (glyph_brush::ab_glyph::Rect, glyph_brush::ab_glyph::Rect) {
+    let width = pixel_coords.width();
+    let height = pixel_coords.height();
+    if width <= 0. || height <= 0. {
+        return (pixel_coords, tex_coords);
+    }
+    let tex_width = tex_coords.width();
+    let tex_height = tex_coords.height();
+    if pixel_coords.min.x < bounds.min.x {
+        tex_coords.min.x += tex_width * (bounds.min.x - pixel_coords.min.x) / width;
+        pixel_coords.min.x = bounds.min.x;
+    }
+    if pixel_coords.max.x > bounds.max.x {
+        tex_coords.max.x -= tex_width * (pixel_coords.max.x - bounds.max.x) / width;
+        pixel_coords.max.x = bounds.max.x;
+    }
+    if pixel_coords.min.y < bounds.min.y {
+        tex_coords.min.y += tex_height * (bounds.min.y - pixel_coords.min.y) / height;
+        pixel_coords.min.y = bounds.min.y;
+    }
+    if pixel_coords.max.y > bounds.max.y {
+        tex_coords.max.y -= tex_height * (pixel_coords.max.y - bounds.max.y) / height;
+        pixel_coords.max.y = bounds.max.y;
+    }
+    if pixel_coords.width() <= 0. || pixel_coords.height() <= 0. {
+        // Entirely outside of the bounds
+        pixel_coords.max = pixel_coords.min;
+        tex_coords.max = tex_coords.min;
+    }
+    (pixel_coords, tex_coords)
+}
+
+// Cuts a quad on the screen to a clip rect, cutting the texture coordinates (which may be flipped) by the same
+// fraction like clip_glyph. None if the quad is entirely outside of the clip.
+fn clip_quad(rect: Rect, uv: [f32; 4], clip: Rect) -> Option<(Rect, [f32; 4])> {
+    let clipped = rect.intersection(clip)?;
+    let start = (clipped.position - rect.position) / rect.size;
+    let size = clipped.size / rect.size;
+    Some((
+        clipped,
+        [
+            uv[0] + uv[2] * start.x,
+            uv[1] + uv[3] * start.y,
+            uv[2] * size.x,
+            uv[3] * size.y,
+        ],
+    ))
+}
+
+pub struct QuadRendererBuilder {
+    fonts: Vec<FontArc>,
+    pixel_perfect: bool,
+    scale: f32,
+    dpi_scale: f32,
+    font_names: HashMap<String, FontId>,
+    glyph_ranges: Option<Vec<RangeInclusive<char>>>,
+}
+
+impl QuadRendererBuilder {
+    pub fn new(fonts: Vec<FontArc>) -> Self {
+        QuadRendererBuilder {
+            fonts,
+            pixel_perfect: false,
+            scale: 1.,
+            dpi_scale: 1.,
+            font_names: HashMap::new(),
+            glyph_ranges: None,
+        }
+    }
+
+    pub fn pixel_perfect(mut self, pixel_perfect: bool) -> Self {
+        self.pixel_perfect = pixel_perfect;
+        self
+    }
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+    pub fn dpi_scale(mut self, dpi_scale: f32) -> Self {
+        self.dpi_scale = dpi_scale;
+        self
+    }
+    pub fn font_name(mut self, name: &str, font: FontId) -> Self {
+        self.font_names.insert(name.to_owned(), font);
+        self
+    }
+    pub fn glyph_ranges(mut self, ranges: Vec<RangeInclusive<char>>) -> Self {
+        self.glyph_ranges = Some(ranges);
+        self
+    }
+
+    pub fn build(self, context: &mut RenderingContext) -> QuadRenderer {
         let vertices: [Vec2; 4] = [
             Vec2 { x: 0., y: 0. },
             Vec2 { x: 1., y: 0. },
@@ -223,82 +840,235 @@ impl QuadRenderer {
             BufferSource::empty::<Quad>(1024),
         );
 
-        let shader = context
-            .new_shader(
-                ShaderSource::Glsl {
-                    vertex: shader::VERTEX,
-                    fragment: shader::FRAGMENT,
-                },
-                shader::meta(),
-            )
-            .unwrap();
-        let pipeline = context.new_pipeline(
-            &[
-                BufferLayout::default(),
-                BufferLayout {
-                    step_func: VertexStep::PerInstance,
-                    ..Default::default()
-                },
-            ],
-            &shader::attributes(),
-            shader,
-            PipelineParams {
-                color_blend: Some(BlendState::new(
-                    Equation::Add,
-                    BlendFactor::Value(BlendValue::SourceAlpha),
-                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
-                )),
-                ..Default::default()
-            },
-        );
+        let pipeline = shader::new_pipeline(context, shader::FRAGMENT).unwrap();
         let white_pixel = Texture::new_rgba8(context, 1, 1, &[255; 4]);
-        let glyph_brush = GlyphBrushBuilder::using_fonts(fonts).build();
-        let glyph_texture = Self::create_glyph_texture(context, glyph_brush.texture_dimensions());
+        let invalid_texture = Texture::invalid(context);
+        let glyphs = GlyphQueue::new(self.fonts);
+        let glyph_texture =
+            QuadRenderer::create_glyph_texture(context, glyphs.brush.texture_dimensions());
 
         QuadRenderer {
-            pixel_perfect,
-            instances: Vec::new(),
-            instance_ranges: Vec::new(),
+            pixel_perfect: self.pixel_perfect,
+            quads: QuadQueue::new(),
             screen_size: Vec2::ONE,
             scroll_offset: Vec2::ZERO,
-            scale: 1.,
-            pipeline,
+            scroll_bounds: None,
+            camera: None,
+            scale: self.scale,
+            dpi_scale: self.dpi_scale,
+            pipelines: vec![pipeline],
             vertex_buffer,
             instance_buffer,
             index_buffer,
             white_pixel,
-            glyph_brush,
+            invalid_texture,
+            glyphs,
+            font_names: self.font_names,
+            glyph_ranges: self.glyph_ranges,
             glyph_texture,
-            glyph_layer: GlyphLayer::new(),
-            glyph_instances: Vec::new(),
+            clip_stack: Vec::new(),
+            stats: RenderStats::default(),
+            debug_shapes: Vec::new(),
+            last_frame_stats: RenderStats::default(),
         }
     }
+}
+
+pub struct QuadRenderer {
+    pixel_perfect: bool,
+    quads: QuadQueue,
+    screen_size: Vec2,
+    scroll_offset: Vec2,
+    scroll_bounds: Option<Rect>,
+    camera: Option<Camera2D>,
+    scale: f32,
+    dpi_scale: f32,
+    pipelines: Vec<Pipeline>,
+    vertex_buffer: BufferId,
+    instance_buffer: BufferId,
+    index_buffer: BufferId,
+    white_pixel: Texture,
+    invalid_texture: Texture,
+    glyphs: GlyphQueue,
+    font_names: HashMap<String, FontId>,
+    glyph_ranges: Option<Vec<RangeInclusive<char>>>,
+    glyph_texture: TextureId,
+    // Clip rects on the screen, each already cut to the ones before it.
+    clip_stack: Vec<Rect>,
+    debug_shapes: Vec<DebugShape>,
+    // Only queued and culled are counted while queueing, the rest comes from the QuadQueue when rendering.
+    stats: RenderStats,
+    last_frame_stats: RenderStats,
+}
 
+impl QuadRenderer {
+    fn create_glyph_texture(
+        context: &mut RenderingContext,
+        (width, height): (u32, u32),
+    ) -> TextureId {
+        context.new_texture(
+            TextureAccess::Static,
+            TextureSource::Empty,
+            TextureParams {
+                kind: TextureKind::Texture2D,
+                format: TextureFormat::Alpha,
+                wrap: TextureWrap::Clamp,
+                min_filter: FilterMode::Linear,
+                mag_filter: FilterMode::Linear,
+                mipmap_filter: MipmapFilterMode::None,
+                width,
+                height,
+                allocate_mipmaps: false,
+            },
+        )
+    }
+
+    pub fn new(context: &mut RenderingContext, fonts: Vec<FontArc>, pixel_perfect: bool) -> Self {
+        QuadRendererBuilder::new(fonts)
+            .pixel_perfect(pixel_perfect)
+            .build(context)
+    }
+
+    // Makes a pipeline with a custom fragment shader, for quads that set RenderQuad::pipeline. The shader can use
+    // these varyings and uniforms (GLSL 100):
+    //     varying lowp vec2 texcoord;
+    //     varying lowp vec4 color;
+    //     varying mediump vec4 data; (RenderQuad::data)
+    //     uniform vec2 screen_size; (in physical pixels)
+    //     uniform float time; (Clock::time in seconds, so it stops while paused)
+    //     uniform float y_direction; (-1 when drawing into a RenderTarget)
+    //     uniform sampler2D tex;
+    //     uniform sampler2D mask; (alpha in the red channel; white for quads)
+    pub fn add_pipeline(
+        &mut self,
+        context: &mut RenderingContext,
+        fragment: &str,
+    ) -> Result<CustomPipeline, ShaderError> {
+        let pipeline = shader::new_pipeline(context, fragment)?;
+        self.pipelines.push(pipeline);
+        Ok(CustomPipeline(self.pipelines.len() - 1))
+    }
+
+    pub fn screen_size(&self) -> Vec2 {
+        self.screen_size
+    }
     pub fn set_screen_size(&mut self, width: f32, height: f32) {
         self.screen_size = Vec2::new(width, height);
     }
 
+    pub fn last_frame_stats(&self) -> RenderStats {
+        self.last_frame_stats
+    }
+
     pub fn scroll_offset(&self) -> Vec2 {
         self.scroll_offset
     }
     pub fn set_scroll_offset(&mut self, scroll: Vec2) {
         self.scroll_offset = scroll;
+        if let Some(bounds) = self.scroll_bounds {
+            // Keep the screen inside the bounds, or centered on them if they're smaller than the screen.
+            let bounds = self.transform(bounds);
+            let min = bounds.position;
+            let max = bounds.position + bounds.size - self.screen_size;
+            let center = bounds.position + (bounds.size - self.screen_size) / 2.;
+            self.scroll_offset = Vec2::select(
+                max.cmpge(min),
+                self.scroll_offset.clamp(min, max.max(min)),
+                center,
+            );
+        }
         if self.pixel_perfect {
             self.scroll_offset.x = self.scroll_offset.x.round();
             self.scroll_offset.y = self.scroll_offset.y.round();
         }
     }
 
+    // Limits the scroll offset so the screen stays inside this area of the world. None (the default) allows any offset.
+    pub fn scroll_bounds(&self) -> Option<Rect> {
+        self.scroll_bounds
+    }
+    pub fn set_scroll_bounds(&mut self, bounds: Option<Rect>) {
+        self.scroll_bounds = bounds;
+        self.set_scroll_offset(self.scroll_offset);
+    }
+
+    // World quads (RenderQuad::scroll) are drawn through the camera instead of the scroll offset while one is set.
+    // The camera position is drawn at the center of the screen, and rotation is in radians. The camera's view_size
+    // isn't used for drawing; for view_rect and follow to match the screen, set it to the screen size divided by
+    // scale and the DPI scale.
+    pub fn camera(&self) -> Option<&Camera2D> {
+        self.camera.as_ref()
+    }
+    pub fn set_camera(&mut self, camera: Option<Camera2D>) {
+        self.camera = camera;
+    }
+
+    // Maps world coordinates to physical screen pixels.
+    pub fn view_matrix(&self) -> Mat4 {
+        let scale = self.scale * self.dpi_scale;
+        match &self.camera {
+            Some(camera) => {
+                Mat4::from_translation((self.screen_size / 2.).extend(0.))
+                    * Mat4::from_rotation_z(camera.rotation)
+                    * Mat4::from_scale(Vec3::new(camera.zoom * scale, camera.zoom * scale, 1.))
+                    * Mat4::from_translation((-camera.position).extend(0.))
+            }
+            None => {
+                Mat4::from_translation((-self.scroll_offset).extend(0.))
+                    * Mat4::from_scale(Vec3::new(scale, scale, 1.))
+            }
+        }
+    }
+    pub fn world_to_screen(&self, point: Vec2) -> Vec2 {
+        self.view_matrix()
+            .transform_point3(point.extend(0.))
+            .truncate()
+    }
+    // E.g. to find what's under the mouse.
+    pub fn screen_to_world(&self, point: Vec2) -> Vec2 {
+        self.view_matrix()
+            .inverse()
+            .transform_point3(point.extend(0.))
+            .truncate()
+    }
+    // The screen pixels covered by a world rect, or by its bounding box if the camera is rotated.
+    fn world_bounds(&self, rect: Rect) -> Rect {
+        let view = self.view_matrix();
+        let corners = [
+            rect.position,
+            rect.position + Vec2::new(rect.width(), 0.),
+            rect.position + Vec2::new(0., rect.height()),
+            rect.position + rect.size,
+        ]
+        .map(|corner| view.transform_point3(corner.extend(0.)).truncate());
+        let min = corners.into_iter().reduce(Vec2::min).unwrap();
+        let max = corners.into_iter().reduce(Vec2::max).unwrap();
+        Rect {
+            position: min,
+            size: max - min,
+        }
+    }
+
     pub fn scale(&self) -> f32 {
         self.scale
     }
+    // Scales quads and text alike, so a GUI laid out at a design resolution can be zoomed as a whole.
     pub fn set_scale(&mut self, scale: f32) {
         self.scale = scale;
     }
 
+    // Everything is queued in logical pixels and scaled by the DPI scale to get physical pixels.
+    // The screen size should be given in physical pixels, as miniquad reports it.
+    pub fn dpi_scale(&self) -> f32 {
+        self.dpi_scale
+    }
+    pub fn set_dpi_scale(&mut self, dpi_scale: f32) {
+        self.dpi_scale = dpi_scale;
+    }
+
     fn transform(&self, mut rect: Rect) -> Rect {
-        rect.position *= self.scale;
-        rect.size *= self.scale;
+        rect.position *= self.scale * self.dpi_scale;
+        rect.size *= self.scale * self.dpi_scale;
         if self.pixel_perfect {
             rect.position.x = rect.position.x.round();
             rect.position.y = rect.position.y.round();
@@ -308,76 +1078,102 @@ impl QuadRenderer {
         rect
     }
     fn process_queued_text(&mut self, context: &mut RenderingContext) {
-        let mut brush_action;
-        loop {
-            brush_action = self.glyph_brush.process_queued(
-                |rect, tex_data| {
-                    // Update part of gpu texture with new glyph alpha values
-                    context.texture_update_part(
-                        self.glyph_texture,
-                        rect.min[0] as i32,
-                        rect.min[1] as i32,
-                        rect.width() as i32,
-                        rect.height() as i32,
-                        tex_data,
-                    );
-                },
-                |glyph_vertex| {
-                    let pos: Rect = glyph_vertex.pixel_coords.into();
-                    let uv: Rect = glyph_vertex.tex_coords.into();
-                    let color = glyph_vertex.extra.color;
-                    (
-                        Quad {
-                            rect: pos.into(),
-                            uv: uv.into(),
-                            color,
-                        },
-                        glyph_vertex.extra.z as usize,
-                    )
-                },
-            );
+        let mut texture = ContextGlyphTexture {
+            context,
+            texture: &mut self.glyph_texture,
+        };
+        self.glyphs.process(self.quads.glyph_layers(), &mut texture);
+    }
 
-            // If the cache texture is too small to fit all the glyphs, resize and try again
-            match brush_action {
-                Ok(_) => break,
-                Err(BrushError::TextureTooSmall { suggested, .. }) => {
-                    // Recreate texture as a larger size to fit more
-                    println!("Resizing glyph texture to {}x{}", suggested.0, suggested.1);
-                    self.glyph_texture = Self::create_glyph_texture(context, suggested);
-                    self.glyph_brush.resize_texture(suggested.0, suggested.1);
-                }
-            }
-        }
+    // Fonts are identified by the order they were loaded in. Naming them means UI code doesn't depend on that order.
+    pub fn add_font_name(&mut self, name: &str, font: FontId) {
+        self.font_names.insert(name.to_owned(), font);
+    }
+    pub fn font_id(&self, name: &str) -> Option<FontId> {
+        self.font_names.get(name).copied()
+    }
 
-        // If the text has changed from what was last drawn, store new instances
-        match brush_action.unwrap() {
-            BrushAction::Draw(instances) => {
-                self.glyph_instances.clear();
-                self.glyph_instances
-                    .resize(self.glyph_layer.layer + 1, Vec::new());
-                for instance in instances {
-                    self.glyph_instances[instance.1].push(instance.0);
-                }
-            }
-            BrushAction::ReDraw => {}
+    // Fonts are always loaded whole, but the glyph cache only holds glyphs that are drawn. Restricting text to the
+    // characters a game's languages need keeps unexpected characters from growing the cache; anything outside the
+    // ranges is drawn as '?'. There are no fallback fonts, so the ranges apply to every font.
+    pub fn set_glyph_ranges(&mut self, ranges: Option<Vec<RangeInclusive<char>>>) {
+        self.glyph_ranges = ranges;
+    }
+    // For sizing GUI views to their text, see Gui::set_text_measurer.
+    pub fn text_measurer(&self) -> TextMeasurer {
+        TextMeasurer::new(self.glyphs.brush.fonts().to_vec())
+    }
+    // The size of the glyph cache texture in bytes (one byte per pixel).
+    pub fn glyph_cache_memory(&self) -> usize {
+        let (width, height) = self.glyphs.brush.texture_dimensions();
+        width as usize * height as usize
+    }
+    fn filter_glyphs(&self, text: &str) -> Option<String> {
+        let ranges = self.glyph_ranges.as_ref()?;
+        let allowed = |c: &char| c.is_control() || ranges.iter().any(|range| range.contains(c));
+        if text.chars().all(|c| allowed(&c)) {
+            return None;
         }
+        Some(
+            text.chars()
+                .map(|c| if allowed(&c) { c } else { '?' })
+                .collect(),
+        )
+    }
+
+    // Grows the glyph cache to fit the given characters up front, so it doesn't have to be resized mid-game.
+    // This needs the rendering context, so call it from GameLoader::prewarm_glyphs. Panics if any text has been queued
+    // for the current frame.
+    pub fn prewarm_glyphs(
+        &mut self,
+        context: &mut RenderingContext,
+        text: &str,
+        font: FontId,
+        font_size: f32,
+    ) {
+        let scale = self.pt_to_px_scale(font, font_size);
+        let section =
+            Section::default().add_text(Text::new(text).with_scale(scale).with_font_id(font));
+        let mut texture = ContextGlyphTexture {
+            context,
+            texture: &mut self.glyph_texture,
+        };
+        self.glyphs.prewarm(section, &mut texture);
     }
 
     pub fn queue(&mut self, quad: RenderQuad) {
-        let texture = quad.texture.unwrap_or(&self.white_pixel).id();
-        let mut rect = self.transform(quad.rect);
-        if rect.width() <= 0. || rect.height() <= 0. {
+        let color = quad.color.into();
+        self.queue_with_color(quad, color);
+    }
+    // Takes the color already converted to the [f32; 4] the shader gets, ignoring quad.color. Converting is cheap
+    // (about 0.7ns a quad), so this only matters for things like particle systems that queue tens of thousands of
+    // quads with precomputed colors.
+    pub fn queue_with_color(&mut self, quad: RenderQuad, color: [f32; 4]) {
+        self.stats.queued += 1;
+        let batch = Batch {
+            texture: quad.texture.unwrap_or(&self.white_pixel).id(),
+            pipeline: quad.pipeline.map(|pipeline| pipeline.0).unwrap_or(0),
+            layer: quad.layer,
+        };
+        // World quads stay in world coordinates for the shader to transform, so the bounds on screen are only used
+        // for culling and text layers.
+        let (mut rect, mut screen_rect) = if quad.scroll {
+            (quad.rect, self.world_bounds(quad.rect))
+        } else {
+            let rect = self.transform(quad.rect);
+            (rect, rect)
+        };
+        if screen_rect.width() <= 0. || screen_rect.height() <= 0. {
+            self.stats.culled += 1;
             return;
         }
-        if quad.scroll {
-            rect.position -= self.scroll_offset;
-        }
-        if rect.x() + rect.width() < 0.
-            || rect.y() + rect.height() < 0.
-            || rect.x() >= self.screen_size.x
-            || rect.y() >= self.screen_size.y
+        if screen_rect.x() + screen_rect.width() < 0.
+            || screen_rect.y() + screen_rect.height() < 0.
+            || screen_rect.x() >= self.screen_size.x
+            || screen_rect.y() >= self.screen_size.y
         {
             // rect is outside of the screen
+            self.stats.culled += 1;
             return;
         }
         let mut uv: [f32; 4] = quad.uv_rect.into();
@@ -389,29 +1185,41 @@ impl QuadRenderer {
             uv[1] += uv[3];
             uv[3] *= -1.;
         }
-
-        self.glyph_layer.finish();
-        self.instances.push(Quad {
-            rect: rect.into(),
-            uv,
-            color: quad.color.into(),
-        });
-        let end = self.instances.len();
-        let mut appended = false;
-        if let Some(InstanceRange::Instances(instance_texture, range)) =
-            self.instance_ranges.last_mut()
-        {
-            if *instance_texture == texture {
-                range.end = end;
-                appended = true;
+        if let (false, Some(clip)) = (quad.scroll, self.clip_stack.last()) {
+            match clip_quad(rect, uv, *clip) {
+                Some((clipped, clipped_uv)) => {
+                    rect = clipped;
+                    screen_rect = clipped;
+                    uv = clipped_uv;
+                }
+                None => {
+                    self.stats.culled += 1;
+                    return;
+                }
             }
         }
-        if !appended {
-            let start = end - 1;
-            self.instance_ranges
-                .push(InstanceRange::Instances(texture, start..end));
+        let instance = Quad {
+            rect: rect.into(),
+            uv,
+            color,
+            data: quad.data.into(),
+            world: if quad.scroll { 1. } else { 0. },
+        };
+        self.quads.push(batch, instance, screen_rect);
+    }
+    // Quads queued between begin_texture_batch and end_texture_batch are regrouped by texture, so each texture in the
+    // batch takes one draw call. This changes the draw order within the batch (but not relative to text), so it's
+    // only correct for quads that don't overlap, or opaque quads where it doesn't matter which is on top. Overlapping
+    // transparent quads can blend in the wrong order.
+    pub fn begin_texture_batch(&mut self) {
+        self.quads.begin_texture_batch();
+    }
+    pub fn end_texture_batch(&mut self) {
+        if !self.quads.end_texture_batch() {
+            eprintln!("end_texture_batch called without begin_texture_batch");
         }
     }
+
     pub fn queue_color(&mut self, rect: Rect, color: LinSrgba) {
         self.queue(RenderQuad {
             color,
@@ -426,44 +1234,227 @@ impl QuadRenderer {
             ..Default::default()
         });
     }
+    // Draws the named part of the atlas. A missing name is reported once, and drawn with the invalid texture so it
+    // stands out.
+    pub fn queue_atlas(&mut self, atlas: &Atlas, name: &str, rect: Rect) {
+        if let Some(uv_rect) = atlas.uv_rect(name) {
+            self.queue(RenderQuad {
+                texture: Some(atlas.texture()),
+                rect,
+                uv_rect,
+                ..Default::default()
+            });
+        } else {
+            if atlas.report_missing(name) {
+                nonfatal_error(&format!("No sprite called {} in atlas", name));
+            }
+            let invalid_texture = self.invalid_texture.clone();
+            self.queue_texture(rect, &invalid_texture);
+        }
+    }
     pub fn queue_all(&mut self, render_list: &mut RenderList) {
-        render_list.0.retain(|renderable| {
+        if render_list.needs_sort {
+            render_list.sort();
+        }
+        render_list.renderables.retain(|renderable| {
             if let Some(renderable) = renderable.try_upgrade() {
-                renderable.get().render(self);
+                let renderable = renderable.get();
+                if let Some((texture, rect)) = renderable.quad() {
+                    self.queue(RenderQuad {
+                        texture,
+                        color: renderable.color(),
+                        rect,
+                        flip: renderable.flip(),
+                        scroll: renderable.scroll(),
+                        ..Default::default()
+                    });
+                }
+                renderable.render(self);
                 true
             } else {
                 false
             }
         });
     }
+    // Quick drawing for visualizing things during development (hitboxes, paths, values), in world coordinates like
+    // other scrolling quads. Shapes are drawn on top of everything else queued this frame, then cleared. In release
+    // builds these do nothing.
+    pub fn debug_rect(&mut self, rect: Rect, color: LinSrgba) {
+        if cfg!(debug_assertions) {
+            self.debug_shapes.push(DebugShape::Rect(rect, color));
+        }
+    }
+    pub fn debug_line(&mut self, a: Vec2, b: Vec2, color: LinSrgba) {
+        if cfg!(debug_assertions) {
+            self.debug_shapes.push(DebugShape::Line(a, b, color));
+        }
+    }
+    pub fn debug_text(&mut self, position: Vec2, text: &str) {
+        if cfg!(debug_assertions) {
+            self.debug_shapes
+                .push(DebugShape::Text(position, text.to_owned()));
+        }
+    }
+    fn queue_debug_rect(&mut self, rect: Rect, color: LinSrgba) {
+        self.queue(RenderQuad {
+            color,
+            rect,
+            layer: i32::MAX,
+            ..Default::default()
+        });
+    }
+    fn queue_debug_shapes(&mut self) {
+        if self.quads.texture_batch_start.is_some() {
+            self.end_texture_batch();
+        }
+        // Debug text gets its own glyph layer, so it can be drawn in the debug layer above the other text.
+        if self
+            .debug_shapes
+            .iter()
+            .any(|shape| matches!(shape, DebugShape::Text(..)))
+        {
+            self.quads.glyph_layer.split();
+        }
+        for shape in std::mem::take(&mut self.debug_shapes) {
+            match shape {
+                DebugShape::Rect(rect, color) => self.queue_debug_rect(rect, color),
+                DebugShape::Line(a, b, color) => {
+                    // Quads can't be rotated, so a diagonal line is drawn as a staircase of 1 pixel thick runs.
+                    let delta = b - a;
+                    let horizontal = delta.x.abs() >= delta.y.abs();
+                    let steps = if horizontal { delta.y } else { delta.x }
+                        .abs()
+                        .ceil()
+                        .max(1.);
+                    for step in 0..steps as usize {
+                        let start = a + delta * (step as f32 / steps);
+                        let end = a + delta * ((step + 1) as f32 / steps);
+                        let min = start.min(end);
+                        let max = start.max(end);
+                        let rect = if horizontal {
+                            Rect::new(min.x, (start.y + end.y) / 2. - 0.5, max.x - min.x + 1., 1.)
+                        } else {
+                            Rect::new((start.x + end.x) / 2. - 0.5, min.y, 1., max.y - min.y + 1.)
+                        };
+                        self.queue_debug_rect(rect, color);
+                    }
+                }
+                DebugShape::Text(position, text) => {
+                    // Without any fonts loaded there's nothing to draw it with.
+                    if self.glyphs.brush.fonts().is_empty() {
+                        continue;
+                    }
+                    let position = self.world_to_screen(position) / (self.scale * self.dpi_scale);
+                    let scale = self.pt_to_px_scale(FontId::default(), 14.);
+                    self.queue_section(
+                        Section {
+                            screen_position: (position.x, position.y),
+                            text: vec![Text::new(&text).with_scale(scale).with_color([1.; 4])],
+                            ..Default::default()
+                        },
+                        i32::MAX,
+                    );
+                }
+            }
+        }
+    }
+
+    // Queues text to be drawn in the quad layer, like RenderQuad::layer.
+    fn queue_section(&mut self, mut section: Section, layer: i32) {
+        let scale = self.scale * self.dpi_scale;
+        section.screen_position.0 *= scale;
+        section.screen_position.1 *= scale;
+        section.bounds.0 *= scale;
+        section.bounds.1 *= scale;
+        let bounds = self.glyphs.brush.glyph_bounds(&section).map(|bounds| {
+            let mut bounds = Rect::from(bounds);
+            if self.pixel_perfect {
+                // Snap the start of the text to a whole pixel, so it's as crisp as the quads around it. Measuring the
+                // laid out text takes alignment into account, e.g. centered text with an odd width.
+                let offset = bounds.position.round() - bounds.position;
+                section.screen_position.0 += offset.x;
+                section.screen_position.1 += offset.y;
+                bounds.position += offset;
+            }
+            bounds
+        });
+        let glyph_layer = self.quads.push_text(bounds, layer);
+        let filtered: Vec<Option<String>> = section
+            .text
+            .iter()
+            .map(|text| self.filter_glyphs(text.text))
+            .collect();
+        let text = section
+            .text
+            .iter()
+            .zip(filtered.iter())
+            .map(|(text, filtered)| Text {
+                text: filtered.as_deref().unwrap_or(text.text),
+                scale: text.scale,
+                font_id: text.font_id,
+                extra: text.extra,
+            })
+            .collect();
+        self.glyphs.queue(
+            Section {
+                screen_position: section.screen_position,
+                bounds: section.bounds,
+                layout: section.layout,
+                text,
+            },
+            glyph_layer,
+            self.clip_stack.last().copied(),
+        );
+    }
+
     pub fn render(&mut self, context: &mut RenderingContext) {
+        self.render_with_direction(context, 1.);
+    }
+    fn render_with_direction(&mut self, context: &mut RenderingContext, y_direction: f32) {
+        self.queue_debug_shapes();
         self.process_queued_text(context);
-        context.apply_pipeline(&self.pipeline);
-        context.apply_uniforms(UniformsSource::table(&shader::Uniforms {
+        self.quads.sort_layers();
+        let uniforms = shader::Uniforms {
             screen_size: self.screen_size,
-        }));
+            time: Clock::current().time().as_secs_f32(),
+            y_direction,
+            pixel_perfect: if self.pixel_perfect { 1. } else { 0. },
+            view: self.view_matrix().to_cols_array(),
+        };
+        let mut current_pipeline = None;
         let white_pixel = self.white_pixel.id();
         let mut bindings = Bindings {
             vertex_buffers: vec![self.vertex_buffer, self.instance_buffer],
             index_buffer: self.index_buffer,
             images: vec![white_pixel, white_pixel],
         };
-        for instance_range in self.instance_ranges.drain(..) {
+        let draw_stats =
+            self.quads
+                .draw_stats(white_pixel, self.glyph_texture, &self.glyphs.instances);
+        for instance_range in self.quads.instance_ranges.drain(..) {
+            let pipeline = match &instance_range {
+                InstanceRange::Instances(batch, _) => batch.pipeline,
+                InstanceRange::Text(..) => 0,
+            };
+            if current_pipeline != Some(pipeline) {
+                context.apply_pipeline(&self.pipelines[pipeline]);
+                context.apply_uniforms(UniformsSource::table(&uniforms));
+                current_pipeline = Some(pipeline);
+            }
+            bindings
+                .images
+                .copy_from_slice(&instance_range.images(white_pixel, self.glyph_texture));
             let num_instances = match instance_range {
-                InstanceRange::Instances(texture, range) => {
-                    bindings.images[0] = texture;
-                    bindings.images[1] = white_pixel;
+                InstanceRange::Instances(_, range) => {
                     let len = range.len();
                     context.buffer_update(
                         self.instance_buffer,
-                        BufferSource::slice(&self.instances[range]),
+                        BufferSource::slice(&self.quads.instances[range]),
                     );
                     len
                 }
-                InstanceRange::Text(layer) => {
-                    bindings.images[0] = white_pixel;
-                    bindings.images[1] = self.glyph_texture;
-                    let instances = &self.glyph_instances[layer];
+                InstanceRange::Text(layer, _) => {
+                    let instances = &self.glyphs.instances[layer];
                     context.buffer_update(self.instance_buffer, BufferSource::slice(instances));
                     instances.len()
                 }
@@ -471,14 +1462,40 @@ impl QuadRenderer {
             context.apply_bindings(&bindings);
             context.draw(0, 6, num_instances.try_into().unwrap());
         }
-        self.instances.clear();
-        self.glyph_layer.reset();
+        self.quads.clear();
+        self.glyphs.end_frame();
+        self.last_frame_stats = RenderStats {
+            queued: self.stats.queued,
+            culled: self.stats.culled,
+            ..draw_stats
+        };
+        self.stats = RenderStats::default();
     }
     pub fn render_pass(&mut self, context: &mut RenderingContext) {
         context.begin_default_pass(Default::default());
         self.render(context);
         context.end_render_pass();
     }
+    // Like render_pass, but draws into the target (cleared to transparent) instead of the screen. Quads are positioned
+    // in the target's pixels, and a screen sized target is resized to the current screen size first.
+    pub fn render_to(&mut self, context: &mut RenderingContext, target: &mut RenderTarget) {
+        if target.is_screen_sized() {
+            target.resize(
+                context,
+                self.screen_size.x as u32,
+                self.screen_size.y as u32,
+            );
+        }
+        let (width, height) = target.size(context);
+        let screen_size = std::mem::replace(
+            &mut self.screen_size,
+            Vec2::new(width as f32, height as f32),
+        );
+        context.begin_pass(Some(target.pass()), PassAction::clear_color(0., 0., 0., 0.));
+        self.render_with_direction(context, -1.);
+        context.end_render_pass();
+        self.screen_size = screen_size;
+    }
 }
 
 impl silica::Renderer for QuadRenderer {
@@ -490,22 +1507,307 @@ impl silica::Renderer for QuadRenderer {
             ..Default::default()
         });
     }
-    fn queue_text(&mut self, mut section: Section) {
-        let layer = self.glyph_layer.next();
-        for text in section.text.iter_mut() {
-            text.extra.z = layer as f32;
-        }
-        self.glyph_brush.queue(section);
-        if !matches!(self.instance_ranges.last(), Some(InstanceRange::Text(_))) {
-            self.instance_ranges.push(InstanceRange::Text(layer));
-        }
+    fn queue_text(&mut self, section: Section) {
+        self.queue_section(section, 0);
+    }
+    // Panics if the texture isn't a gristmill Texture.
+    fn queue_texture(
+        &mut self,
+        texture: &dyn std::any::Any,
+        point: Point<f32>,
+        size: Size<f32>,
+        uv: silica::taffy::Rect<f32>,
+        color: LinSrgba,
+    ) {
+        let texture = texture
+            .downcast_ref::<Texture>()
+            .expect("QuadRenderer can only draw gristmill Textures");
+        self.queue(RenderQuad {
+            texture: Some(texture),
+            color,
+            rect: Rect::new(point.x, point.y, size.width, size.height),
+            uv_rect: Rect::new(uv.left, uv.top, uv.right - uv.left, uv.bottom - uv.top),
+            scroll: false,
+            ..Default::default()
+        });
     }
-    fn pt_to_px_scale(&self, font: silica::FontId, pt_size: f32) -> PxScale {
+    // Clipping is done while queueing, by cutting quads and glyphs to the clip rect, so it doesn't split up draw
+    // calls the way a scissor rect would. It only applies to screen quads; world quads are never clipped.
+    fn push_clip(&mut self, point: Point<f32>, size: Size<f32>) {
+        let clip = self.transform(Rect::new(point.x, point.y, size.width, size.height));
+        let clip = match self.clip_stack.last() {
+            Some(outer) => outer.intersection(clip).unwrap_or(Rect {
+                position: clip.position,
+                size: Vec2::ZERO,
+            }),
+            None => clip,
+        };
+        self.clip_stack.push(clip);
+    }
+    fn pop_clip(&mut self) {
+        self.clip_stack.pop().expect("pop_clip without push_clip");
+    }
+    fn pt_to_px_scale(&self, font: FontId, pt_size: f32) -> PxScale {
         let font = self
-            .glyph_brush
+            .glyphs
+            .brush
             .fonts()
             .get(font.0)
             .expect("invalid FontId");
-        font.pt_to_px_scale(pt_size).unwrap()
+        let scale = font.pt_to_px_scale(pt_size).unwrap();
+        PxScale {
+            x: scale.x * self.scale * self.dpi_scale,
+            y: scale.y * self.scale * self.dpi_scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct TestTexture {
+        resizes: usize,
+        updates: usize,
+    }
+
+    impl GlyphTexture for TestTexture {
+        fn resize(&mut self, _size: (u32, u32)) {
+            self.resizes += 1;
+        }
+        fn update(&mut self, _rect: glyph_brush::Rectangle<u32>, _data: &[u8]) {
+            self.updates += 1;
+        }
+    }
+
+    fn glyph_queue() -> GlyphQueue {
+        let font =
+            FontArc::try_from_slice(include_bytes!("../../fonts/OpenSans-Regular.ttf")).unwrap();
+        GlyphQueue::new(vec![font])
+    }
+
+    fn section(text: &str) -> Section<'_> {
+        Section::default().add_text(Text::new(text).with_scale(32.))
+    }
+
+    #[test]
+    fn prewarm_then_render() {
+        let mut glyphs = glyph_queue();
+        let mut texture = TestTexture::default();
+        glyphs.prewarm(section("abcdefghijklmnopqrstuvwxyz"), &mut texture);
+        assert!(texture.updates > 0);
+        assert!(glyphs.instances.is_empty());
+
+        // A frame drawing some of the prewarmed glyphs on layer 1, without uploading them again.
+        let updates = texture.updates;
+        glyphs.queue(section("abc"), 1, None);
+        glyphs.process(2, &mut texture);
+        assert_eq!(texture.updates, updates);
+        assert!(glyphs.instances[0].is_empty());
+        assert_eq!(glyphs.instances[1].len(), 3);
+        glyphs.end_frame();
+    }
+
+    #[test]
+    #[should_panic(expected = "after text is queued")]
+    fn prewarm_mid_frame() {
+        let mut glyphs = glyph_queue();
+        glyphs.queue(section("abc"), 0, None);
+        glyphs.prewarm(section("def"), &mut TestTexture::default());
+    }
+
+    #[test]
+    fn clipped_text() {
+        let mut glyphs = glyph_queue();
+        let mut texture = TestTexture::default();
+        glyphs.queue(section("abc"), 0, None);
+        glyphs.queue(section("abc"), 0, Some(Rect::new(-100., -100., 1., 1.)));
+        glyphs.process(1, &mut texture);
+        assert_eq!(glyphs.instances[0].len(), 3);
+        glyphs.end_frame();
+
+        // The same text with different clips isn't laid out again, but is still clipped again.
+        glyphs.queue(section("abc"), 0, None);
+        glyphs.queue(section("abc"), 0, None);
+        glyphs.process(1, &mut texture);
+        assert_eq!(glyphs.instances[0].len(), 6);
+    }
+
+    // Raw ids, since there's no context to make textures with. They're never drawn.
+    fn test_texture(id: u32) -> TextureId {
+        TextureId::from_raw_id(RawId::OpenGl(id))
+    }
+
+    // A row of quads alternating between two textures, like tiles from two tilesets.
+    fn queue_alternating(quads: &mut QuadQueue) {
+        for i in 0..10 {
+            let batch = Batch {
+                texture: test_texture(1 + i % 2),
+                pipeline: 0,
+                layer: 0,
+            };
+            let rect = Rect::new(i as f32 * 10., 0., 10., 10.);
+            let quad = Quad {
+                rect: rect.into(),
+                uv: [0., 0., 1., 1.],
+                color: [1.; 4],
+                data: [0.; 4],
+                world: 0.,
+            };
+            quads.push(batch, quad, rect);
+        }
+    }
+
+    #[test]
+    fn texture_batch_draw_calls() {
+        let (white_pixel, glyph_texture) = (test_texture(0), test_texture(100));
+        let mut quads = QuadQueue::new();
+        queue_alternating(&mut quads);
+        let stats = quads.draw_stats(white_pixel, glyph_texture, &[]);
+        assert_eq!((stats.draw_calls, stats.texture_switches), (10, 9));
+        quads.clear();
+
+        quads.begin_texture_batch();
+        queue_alternating(&mut quads);
+        assert!(quads.end_texture_batch());
+        let stats = quads.draw_stats(white_pixel, glyph_texture, &[]);
+        assert_eq!((stats.draw_calls, stats.texture_switches), (2, 1));
+        assert_eq!(stats.quads, 10);
+        let order: Vec<f32> = quads.instances.iter().map(|quad| quad.rect[0]).collect();
+        assert_eq!(order, [0., 20., 40., 60., 80., 10., 30., 50., 70., 90.]);
+    }
+
+    #[test]
+    fn end_texture_batch_without_begin() {
+        let (white_pixel, glyph_texture) = (test_texture(0), test_texture(100));
+        let mut quads = QuadQueue::new();
+        queue_alternating(&mut quads);
+        assert!(!quads.end_texture_batch());
+        let stats = quads.draw_stats(white_pixel, glyph_texture, &[]);
+        assert_eq!(stats.draw_calls, 10);
+
+        // A batch started afterwards still works, and ends only once.
+        quads.begin_texture_batch();
+        queue_alternating(&mut quads);
+        assert!(quads.end_texture_batch());
+        assert!(!quads.end_texture_batch());
+        let stats = quads.draw_stats(white_pixel, glyph_texture, &[]);
+        assert_eq!(stats.draw_calls, 12);
+    }
+
+    #[test]
+    fn labels_drawn_together() {
+        // A screen of 30 buttons, each a background quad with a label on it, queued the way QuadRenderer does.
+        let (white_pixel, glyph_texture) = (test_texture(0), test_texture(100));
+        let background = Batch {
+            texture: white_pixel,
+            pipeline: 0,
+            layer: 0,
+        };
+        let mut quads = QuadQueue::new();
+        let mut glyphs = glyph_queue();
+        for i in 0..30 {
+            let rect = Rect::new(0., i as f32 * 40., 200., 30.);
+            let quad = Quad {
+                rect: rect.into(),
+                uv: [0., 0., 1., 1.],
+                color: [1.; 4],
+                data: [0.; 4],
+                world: 0.,
+            };
+            quads.push(background, quad, rect);
+            let label = section("Button").with_screen_position((10., i as f32 * 40.));
+            let bounds = glyphs.brush.glyph_bounds(&label).map(Rect::from);
+            let glyph_layer = quads.push_text(bounds, 0);
+            glyphs.queue(label, glyph_layer, None);
+        }
+        glyphs.process(quads.glyph_layers(), &mut TestTexture::default());
+        let stats = quads.draw_stats(white_pixel, glyph_texture, &glyphs.instances);
+        // One draw call for the backgrounds and one for all of the labels.
+        assert_eq!(stats.draw_calls, 2);
+        assert_eq!((stats.quads, stats.text_glyphs), (30, 30 * 6));
+    }
+
+    #[test]
+    fn labels_share_glyph_layer() {
+        // A screen of 30 buttons, each a background quad with a label on it.
+        let mut glyph_layer = GlyphLayer::new();
+        for i in 0..30 {
+            let rect = Rect::new(0., i as f32 * 40., 200., 30.);
+            glyph_layer.add_quad(rect);
+            assert_eq!(glyph_layer.next(), 0);
+            glyph_layer.add_text(Rect::new(10., i as f32 * 40. + 5., 100., 20.));
+        }
+        // A quad over one of the labels, like a tooltip, puts the text after it on another layer.
+        glyph_layer.add_quad(Rect::new(50., 45., 100., 100.));
+        assert_eq!(glyph_layer.next(), 1);
+        glyph_layer.add_text(Rect::new(55., 50., 50., 20.));
+        glyph_layer.add_quad(Rect::new(0., 400., 10., 10.));
+        assert_eq!(glyph_layer.next(), 1);
+        glyph_layer.reset();
+        assert_eq!(glyph_layer.next(), 0);
+    }
+
+    #[test]
+    fn glyph_cut_midway() {
+        use glyph_brush::ab_glyph::{point, Rect as GlyphRect};
+        let glyph = GlyphRect {
+            min: point(0., 0.),
+            max: point(10., 20.),
+        };
+        let tex = GlyphRect {
+            min: point(0.5, 0.5),
+            max: point(0.6, 0.7),
+        };
+        let bounds = GlyphRect {
+            min: point(-5., 0.),
+            max: point(5., 10.),
+        };
+        let (glyph, tex) = clip_glyph(glyph, tex, bounds);
+        assert_eq!(Rect::from(glyph), Rect::new(0., 0., 5., 10.));
+        assert!((tex.max.x - 0.55).abs() < 1e-6 && (tex.max.y - 0.6).abs() < 1e-6);
+        assert_eq!(tex.min, point(0.5, 0.5));
+
+        let outside = GlyphRect {
+            min: point(20., 0.),
+            max: point(30., 10.),
+        };
+        let (glyph, _) = clip_glyph(outside, tex, bounds);
+        assert_eq!(glyph.width(), 0.);
+    }
+
+    #[test]
+    fn oversized_text_in_tiny_bounds() {
+        let mut glyphs = glyph_queue();
+        let bounds = Rect::new(0., 0., 30., 20.);
+        glyphs.queue(section("oversized").with_bounds((30., 20.)), 0, None);
+        glyphs.process(1, &mut TestTexture::default());
+        assert!(!glyphs.instances[0].is_empty());
+        for quad in glyphs.instances[0].iter() {
+            let rect = Rect::from(quad.rect);
+            assert!(rect.size.x <= 0. || bounds.intersection(rect) == Some(rect));
+        }
+    }
+
+    #[test]
+    fn builder_options() {
+        let font =
+            FontArc::try_from_slice(include_bytes!("../../fonts/OpenSans-Regular.ttf")).unwrap();
+        let builder = QuadRendererBuilder::new(vec![font])
+            .pixel_perfect(true)
+            .scale(2.)
+            .dpi_scale(1.5)
+            .font_name("body", FontId(0))
+            .glyph_ranges(vec!['a'..='z']);
+        assert!(builder.pixel_perfect);
+        assert_eq!((builder.scale, builder.dpi_scale), (2., 1.5));
+        assert_eq!(builder.font_names.get("body"), Some(&FontId(0)));
+        assert_eq!(builder.glyph_ranges, Some(vec!['a'..='z']));
+
+        let defaults = QuadRendererBuilder::new(Vec::new());
+        assert!(!defaults.pixel_perfect);
+        assert_eq!((defaults.scale, defaults.dpi_scale), (1., 1.));
+        assert!(defaults.font_names.is_empty() && defaults.glyph_ranges.is_none());
     }
 }
@@ -1,12 +1,31 @@
-use serde::Deserialize;
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
 use std::{collections::HashMap, time::Duration};
 
-use crate::{asset, math::Vec2, two::Rect, RenderingContext, Texture};
+use grist::{impl_add_event_listener, Event};
+
+use crate::{
+    asset::{self, AssetError},
+    math::Vec2,
+    two::{Anchor, Rect},
+    RenderingContext, Texture,
+};
 
 #[derive(Deserialize)]
 struct SpriteSheetDefinition {
+    // The frame rate for frames without their own duration.
     fps: f32,
-    frames: HashMap<String, Vec<Rect>>,
+    frames: HashMap<String, Vec<FrameDefinition>>,
+    // Anchor for all animations, which can be overridden per animation.
+    #[serde(default)]
+    anchor: Anchor,
+    #[serde(default)]
+    anchors: HashMap<String, Anchor>,
+    // Animations loop unless they're set to false here. Ones that don't stop on their last frame.
+    #[serde(default)]
+    loops: HashMap<String, bool>,
 }
 
 impl Default for SpriteSheetDefinition {
@@ -14,39 +33,290 @@ impl Default for SpriteSheetDefinition {
         Self {
             fps: 24.,
             frames: Default::default(),
+            anchor: Anchor::TopLeft,
+            anchors: Default::default(),
+            loops: Default::default(),
+        }
+    }
+}
+
+// A frame is either just a rect, or a rect with how long it's shown for in seconds.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(untagged)]
+enum FrameDefinition {
+    Timed {
+        rect: Rect,
+        #[serde(default)]
+        duration: Option<f32>,
+    },
+    Rect(Rect),
+}
+
+#[derive(Clone, Copy)]
+struct Frame {
+    rect: Rect,
+    duration: f32,
+}
+
+// The JSON written by Aseprite's sprite sheet export, or TexturePacker's JSON formats. Frames can be a list or a map
+// of file names to frames (kept in file order, which is the frame order).
+#[derive(Deserialize)]
+struct PackedSheet {
+    #[serde(deserialize_with = "packed_frames")]
+    frames: Vec<PackedFrame>,
+    #[serde(default)]
+    meta: PackedMeta,
+}
+
+#[derive(Deserialize)]
+struct PackedFrame {
+    #[serde(default)]
+    filename: String,
+    frame: PackedRect,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    trimmed: bool,
+    // Milliseconds. Only Aseprite writes durations.
+    duration: Option<f32>,
+}
+
+impl PackedFrame {
+    fn definition(&self) -> FrameDefinition {
+        FrameDefinition::Timed {
+            rect: Rect::new(self.frame.x, self.frame.y, self.frame.w, self.frame.h),
+            duration: self
+                .duration
+                .filter(|duration| *duration > 0.)
+                .map(|duration| duration / 1000.),
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Deserialize)]
+struct PackedRect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+#[derive(Deserialize, Default)]
+struct PackedMeta {
+    #[serde(default, rename = "frameTags")]
+    frame_tags: Vec<PackedTag>,
+}
+
+#[derive(Deserialize)]
+struct PackedTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+}
+
+fn packed_frames<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PackedFrame>, D::Error> {
+    struct FramesVisitor;
+    impl<'de> Visitor<'de> for FramesVisitor {
+        type Value = Vec<PackedFrame>;
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "a list or map of frames")
+        }
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(
+            self,
+            mut seq: A,
+        ) -> Result<Self::Value, A::Error> {
+            let mut frames = Vec::new();
+            while let Some(frame) = seq.next_element()? {
+                frames.push(frame);
+            }
+            Ok(frames)
+        }
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut frames = Vec::new();
+            while let Some((filename, mut frame)) = map.next_entry::<String, PackedFrame>()? {
+                frame.filename = filename;
+                frames.push(frame);
+            }
+            Ok(frames)
+        }
+    }
+    deserializer.deserialize_any(FramesVisitor)
+}
+
+impl PackedSheet {
+    // Frame tags become animations. Without tags (e.g. from TexturePacker), each frame is a one frame animation named
+    // after its file, without the extension. Frames keep their durations from Aseprite.
+    fn into_definition(self) -> std::result::Result<SpriteSheetDefinition, String> {
+        let mut rects = Vec::with_capacity(self.frames.len());
+        for frame in self.frames.iter() {
+            if frame.rotated || frame.trimmed {
+                return Err(format!(
+                    "Frame {} is rotated or trimmed, which isn't supported. Export without rotation and trimming.",
+                    frame.filename
+                ));
+            }
+            rects.push(frame.definition());
+        }
+        let mut frames = HashMap::new();
+        if self.meta.frame_tags.is_empty() {
+            for (frame, rect) in self.frames.iter().zip(rects.iter()) {
+                let name = match frame.filename.rsplit_once('.') {
+                    Some((name, _)) => name,
+                    None => &frame.filename,
+                };
+                frames.insert(name.to_owned(), vec![*rect]);
+            }
+        }
+        for tag in self.meta.frame_tags {
+            if tag.from > tag.to || tag.to >= rects.len() {
+                return Err(format!("Tag {} has an invalid frame range", tag.name));
+            }
+            let forward = rects[tag.from..=tag.to].iter().copied();
+            let animation: Vec<FrameDefinition> = match tag.direction.as_str() {
+                "reverse" => forward.rev().collect(),
+                "pingpong" => {
+                    // Back down without repeating the last frame, or the first (the loop plays it again).
+                    let back = rects[tag.from..tag.to].iter().skip(1).rev().copied();
+                    forward.chain(back).collect()
+                }
+                _ => forward.collect(),
+            };
+            frames.insert(tag.name, animation);
+        }
+        Ok(SpriteSheetDefinition {
+            frames,
+            ..Default::default()
+        })
+    }
+}
+
+// Returned by set_animation for an animation the sprite sheet doesn't have.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AnimationError(String);
+
+impl AnimationError {
+    pub fn animation(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for AnimationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No animation called {}", self.0)
+    }
+}
+
+impl std::error::Error for AnimationError {}
+
 pub struct SpriteSheet {
     texture: Texture,
     texture_size: Vec2,
-    frames: HashMap<String, Vec<Rect>>,
+    frames: HashMap<String, Vec<Frame>>,
+    anchor: Anchor,
+    anchors: HashMap<String, Anchor>,
+    loops: HashMap<String, bool>,
     current_frame: Rect,
     current_animation: String,
     current_animation_frame: usize,
-    frame_duration: f32,
+    animation_loops: usize,
+    finished: bool,
     frame_time: f32,
+    finished_event: Event<()>,
+}
+
+// Clones don't get the finished listeners.
+impl Clone for SpriteSheet {
+    fn clone(&self) -> Self {
+        SpriteSheet {
+            texture: self.texture.clone(),
+            texture_size: self.texture_size,
+            frames: self.frames.clone(),
+            anchor: self.anchor,
+            anchors: self.anchors.clone(),
+            loops: self.loops.clone(),
+            current_frame: self.current_frame,
+            current_animation: self.current_animation.clone(),
+            current_animation_frame: self.current_animation_frame,
+            animation_loops: self.animation_loops,
+            finished: self.finished,
+            frame_time: self.frame_time,
+            finished_event: Event::new(),
+        }
+    }
 }
 
 impl SpriteSheet {
     pub fn load(context: &mut RenderingContext, file: &str) -> asset::Result<Self> {
         let image_file = format!("{}.png", file);
         let definition_file = format!("{}.yaml", file);
+        let json_file = format!("{}.json", file);
         let texture = asset::load_png_file(context, "images", &image_file)?;
+        // The same definition can be written as JSON instead, if there's no YAML file.
+        let frames: SpriteSheetDefinition =
+            if !asset::exists("images", &definition_file) && asset::exists("images", &json_file) {
+                asset::load_json_file("images", &json_file)?
+            } else {
+                asset::load_yaml_file("images", &definition_file)?
+            };
+        Ok(Self::from_definition(context, texture, frames))
+    }
+    // Loads a sprite sheet exported by Aseprite or TexturePacker as <file>.png and <file>.json (in either the hash or
+    // array JSON format).
+    pub fn load_packed(context: &mut RenderingContext, file: &str) -> asset::Result<Self> {
+        let image_file = format!("{}.png", file);
+        let definition_file = format!("{}.json", file);
+        let sheet: PackedSheet = asset::load_json_file("images", &definition_file)?;
+        let frames = sheet.into_definition().map_err(|info| {
+            let path = asset::get_path("images", &definition_file);
+            AssetError::new_format(path, false, info)
+        })?;
+        let texture = asset::load_png_file(context, "images", &image_file)?;
+        Ok(Self::from_definition(context, texture, frames))
+    }
+    fn from_definition(
+        context: &mut RenderingContext,
+        texture: Texture,
+        frames: SpriteSheetDefinition,
+    ) -> Self {
         let texture_size = context.texture_size(texture.id());
-        let frames: SpriteSheetDefinition = asset::load_yaml_file("images", &definition_file)?;
-        Ok(SpriteSheet {
+        let default_duration = 1. / frames.fps;
+        let animations = frames
+            .frames
+            .into_iter()
+            .map(|(name, animation)| {
+                let animation = animation
+                    .into_iter()
+                    .map(|frame| match frame {
+                        FrameDefinition::Timed { rect, duration } => Frame {
+                            rect,
+                            duration: duration.unwrap_or(default_duration),
+                        },
+                        FrameDefinition::Rect(rect) => Frame {
+                            rect,
+                            duration: default_duration,
+                        },
+                    })
+                    .collect();
+                (name, animation)
+            })
+            .collect();
+        SpriteSheet {
             texture,
             texture_size: Vec2::new(texture_size.0 as f32, texture_size.1 as f32),
-            frames: frames.frames,
+            frames: animations,
+            anchor: frames.anchor,
+            anchors: frames.anchors,
+            loops: frames.loops,
             current_frame: Rect::ZERO,
             current_animation: String::new(),
             current_animation_frame: 0,
-            frame_duration: 1. / frames.fps,
+            animation_loops: 0,
+            finished: false,
             frame_time: 0.,
-        })
+            finished_event: Event::new(),
+        }
     }
 
     pub fn texture(&self) -> &Texture {
@@ -61,28 +331,106 @@ impl SpriteSheet {
         rect.size /= self.texture_size;
         rect
     }
-    pub fn set_animation(&mut self, animation: &str) {
+    pub fn current_anchor(&self) -> Anchor {
+        self.anchors
+            .get(&self.current_animation)
+            .copied()
+            .unwrap_or(self.anchor)
+    }
+    // Where to draw the current frame so that its anchor point is at position.
+    pub fn draw_rect(&self, position: Vec2) -> Rect {
+        Rect::from_anchor(self.current_frame.size, self.current_anchor(), position)
+    }
+    // In no particular order.
+    pub fn animations(&self) -> impl Iterator<Item = &str> {
+        self.frames.keys().map(String::as_str)
+    }
+    pub fn has_animation(&self, animation: &str) -> bool {
+        self.frames.contains_key(animation)
+    }
+    // Empty if no animation has been set.
+    pub fn current_animation(&self) -> &str {
+        &self.current_animation
+    }
+    pub fn current_frame_index(&self) -> usize {
+        self.current_animation_frame
+    }
+    // How many times the current animation has played all the way through. At most 1 for an animation that doesn't
+    // loop.
+    pub fn animation_loops(&self) -> usize {
+        self.animation_loops
+    }
+    pub fn animation_looping(&self, animation: &str) -> bool {
+        self.loops.get(animation).copied().unwrap_or(true)
+    }
+    // True once an animation that doesn't loop has shown its last frame for a full frame duration. It then stays on
+    // that frame until the animation is set again.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+    // Starts the animation from its first frame. If the sprite sheet doesn't have it, the current animation keeps
+    // playing.
+    pub fn set_animation(&mut self, animation: &str) -> Result<(), AnimationError> {
+        if !self.has_animation(animation) {
+            return Err(AnimationError(animation.to_owned()));
+        }
         self.current_animation = animation.to_string();
+        self.animation_loops = 0;
+        self.finished = false;
         self.frame_time = 0.0;
         self.set_animation_frame(0);
+        Ok(())
     }
+    // Does nothing if no animation is set.
     pub fn set_animation_frame(&mut self, frame: usize) {
         if let Some(frames) = self.frames.get(&self.current_animation) {
-            self.current_animation_frame = frame % frames.len();
-            self.current_frame = frames[self.current_animation_frame];
-        } else {
-            if self.current_animation.is_empty() {
-                eprintln!("Animation not set");
-            } else {
-                eprintln!("No animation called {}", self.current_animation);
+            if frames.is_empty() {
+                self.current_animation_frame = 0;
+                self.current_frame = Rect::ZERO;
+                return;
             }
+            self.current_animation_frame = frame % frames.len();
+            self.current_frame = frames[self.current_animation_frame].rect;
         }
     }
     pub fn animate(&mut self, frame_time: Duration) {
+        if self.finished {
+            return;
+        }
         self.frame_time += frame_time.as_secs_f32();
-        if self.frame_time >= self.frame_duration {
-            self.frame_time -= self.frame_duration;
+        let (len, frame_duration) = match self.frames.get(&self.current_animation) {
+            Some(frames) => (
+                frames.len(),
+                frames
+                    .get(self.current_animation_frame)
+                    .map(|frame| frame.duration)
+                    .unwrap_or_default(),
+            ),
+            None => return,
+        };
+        if self.frame_time >= frame_duration {
+            self.frame_time -= frame_duration;
+            if self.current_animation_frame + 1 >= len
+                && !self.animation_looping(&self.current_animation)
+            {
+                self.finished = true;
+                self.frame_time = 0.;
+                self.animation_loops = 1;
+                self.finished_event.emit(&());
+                return;
+            }
             self.set_animation_frame(self.current_animation_frame + 1);
+            if self.current_animation_frame == 0 {
+                self.animation_loops += 1;
+            }
         }
     }
 }
+
+impl_add_event_listener!(
+    SpriteSheet,
+    finished_event,
+    (),
+    add_finished_listener,
+    remove_finished_listener
+);
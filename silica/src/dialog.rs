@@ -0,0 +1,266 @@
+use grist::{impl_add_event_listener, obj_upcast, Event, Obj};
+use palette::LinSrgba;
+use taffy::prelude::*;
+
+use crate::{
+    view::{
+        button::{Button, SimpleButtonView},
+        label::Label,
+    },
+    Gui, GuiRenderer, HorizontalAlign, Key, SimpleColors, View,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DialogResult {
+    Button(usize),
+    Cancel,
+}
+
+pub struct Dialog {
+    default_button: usize,
+    result: Option<DialogResult>,
+    resolved: Event<DialogResult>,
+}
+
+impl Dialog {
+    fn new() -> Self {
+        Dialog {
+            default_button: 0,
+            result: None,
+            resolved: Event::new(),
+        }
+    }
+
+    pub fn default_button(&self) -> usize {
+        self.default_button
+    }
+    pub fn set_default_button(&mut self, index: usize) {
+        self.default_button = index;
+    }
+    // Closes the dialog with the result. The dialog is removed and resolved is emitted at the Gui's next layout or
+    // input event, so it's safe to call from the dialog's own listeners.
+    pub fn resolve(&mut self, result: DialogResult) {
+        if self.result.is_none() {
+            self.result = Some(result);
+        }
+    }
+
+    pub(crate) fn handle_key(&mut self, key: Key, repeat: bool) -> bool {
+        if repeat && matches!(key, Key::Enter | Key::Escape) {
+            return true;
+        }
+        match key {
+            Key::Enter => self.resolve(DialogResult::Button(self.default_button)),
+            Key::Escape => self.resolve(DialogResult::Cancel),
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl_add_event_listener!(
+    Dialog,
+    resolved,
+    DialogResult,
+    add_resolved_listener,
+    remove_resolved_listener
+);
+
+struct Backdrop {
+    color: LinSrgba,
+}
+
+impl View for Backdrop {
+    fn render(&self, renderer: &mut GuiRenderer) {
+        renderer.set_color(self.color);
+        renderer.draw_rect();
+    }
+}
+
+#[derive(Default)]
+struct DialogPanel {
+    colors: SimpleColors,
+}
+
+impl View for DialogPanel {
+    fn render(&self, renderer: &mut GuiRenderer) {
+        renderer.set_color(self.colors.bg_normal);
+        renderer.draw_rect();
+        renderer.set_color(self.colors.fg_normal);
+        renderer.draw_border(Rect::length(1.));
+    }
+}
+
+impl Gui {
+    pub fn show_dialog(&mut self, message: &str, buttons: &[&str]) -> Obj<Dialog> {
+        if let Some((node, _)) = self.dialog.take() {
+            self.set_overlay(None);
+            self.destroy_tree(node);
+        }
+
+        let dialog = Obj::new(Dialog::new());
+        let overlay = self.add_root();
+        self.set_style(
+            overlay,
+            Style {
+                justify_content: Some(JustifyContent::Center),
+                align_items: Some(AlignItems::Center),
+                ..Default::default()
+            },
+        );
+        let backdrop = Obj::new(Backdrop {
+            color: LinSrgba::new(0., 0., 0., 0.5),
+        });
+        self.views.insert(overlay, obj_upcast!(backdrop).upgrade());
+
+        let panel = self.add_view(
+            overlay,
+            Obj::new(DialogPanel::default()),
+            Style {
+                flex_direction: FlexDirection::Column,
+                align_items: Some(AlignItems::Center),
+                padding: Rect::length(16.),
+                gap: Size::length(16.),
+                size: Size {
+                    width: length(360.),
+                    height: auto(),
+                },
+                ..Default::default()
+            },
+        );
+        let mut label = Label::with_text(message);
+        label.set_halign(HorizontalAlign::Center);
+        label.set_wrap(true);
+        // Long messages wrap, and the label grows to fit them once the Gui has a text measurer.
+        self.add_view(
+            panel,
+            Obj::new(label),
+            Style {
+                size: Size {
+                    width: percent(1.),
+                    height: auto(),
+                },
+                min_size: Size {
+                    width: auto(),
+                    height: length(32.),
+                },
+                ..Default::default()
+            },
+        );
+        let button_row = self.add_node(
+            panel,
+            Style {
+                flex_direction: FlexDirection::Row,
+                gap: Size::length(16.),
+                ..Default::default()
+            },
+        );
+        for (index, text) in buttons.iter().enumerate() {
+            let mut button = Button::with_label(text, SimpleButtonView::default());
+            let dialog = dialog.clone();
+            button.add_pressed_listener(move |&()| {
+                dialog.get_mut().resolve(DialogResult::Button(index));
+            });
+            self.add_view_control(
+                button_row,
+                Obj::new(button),
+                Style {
+                    size: Size::from_lengths(96., 32.),
+                    ..Default::default()
+                },
+            );
+        }
+
+        self.dialog = Some((overlay, dialog.clone()));
+        self.set_overlay(Some(overlay));
+        dialog
+    }
+
+    pub(crate) fn update_dialog(&mut self) {
+        let result = match &self.dialog {
+            Some((_, dialog)) => dialog.get_mut().result.take(),
+            None => return,
+        };
+        if let Some(result) = result {
+            let (node, dialog) = self.dialog.take().unwrap();
+            self.set_overlay(None);
+            self.destroy_tree(node);
+            dialog.get_mut().resolved.emit(&result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TextMeasurer;
+    use std::{cell::RefCell, rc::Rc};
+
+    fn results(dialog: &Obj<Dialog>) -> Rc<RefCell<Vec<DialogResult>>> {
+        let results = Rc::new(RefCell::new(Vec::new()));
+        let record = results.clone();
+        dialog
+            .get_mut()
+            .add_resolved_listener(move |result| record.borrow_mut().push(*result));
+        results
+    }
+
+    #[test]
+    fn keys_resolve() {
+        let mut gui = Gui::new();
+        gui.set_screen_size(800., 600.);
+        let nodes = gui.layout.total_node_count();
+        for (key, expected) in [
+            (Key::Enter, DialogResult::Button(1)),
+            (Key::Escape, DialogResult::Cancel),
+        ] {
+            let dialog = gui.show_dialog("Quit without saving?", &["Cancel", "Quit"]);
+            dialog.get_mut().set_default_button(1);
+            let results = results(&dialog);
+            assert!(gui.handle_key(key, true));
+            assert!(results.borrow().is_empty());
+            assert!(gui.handle_key(key, false));
+            assert_eq!(*results.borrow(), [expected]);
+            // The overlay and everything in it is gone.
+            assert_eq!(gui.overlay(), None);
+            assert!(gui.dialog.is_none());
+            assert_eq!(gui.layout.total_node_count(), nodes);
+            assert!(gui.views.is_empty() && gui.controls.is_empty());
+        }
+    }
+
+    #[test]
+    fn resolved_from_code() {
+        let mut gui = Gui::new();
+        gui.set_screen_size(800., 600.);
+        let dialog = gui.show_dialog("Saved", &["OK"]);
+        let results = results(&dialog);
+        dialog.get_mut().resolve(DialogResult::Button(0));
+        assert!(gui.overlay().is_some());
+        gui.layout();
+        assert_eq!(gui.overlay(), None);
+        assert_eq!(*results.borrow(), [DialogResult::Button(0)]);
+    }
+
+    #[test]
+    fn long_message_fits() {
+        let font = glyph_brush::ab_glyph::FontArc::try_from_slice(include_bytes!(
+            "../../gristmill/fonts/OpenSans-Regular.ttf"
+        ))
+        .unwrap();
+        let mut gui = Gui::new();
+        gui.set_screen_size(800., 600.);
+        gui.set_text_measurer(TextMeasurer::new(vec![font]));
+        let message = "The save file couldn't be written, because the disk is full. Free up some space and try again, \
+                       or keep playing without saving.";
+        gui.show_dialog(message, &["OK"]);
+        let overlay = gui.overlay().unwrap();
+        let panel = gui.layout.children(overlay).unwrap()[0];
+        let label = gui.layout.children(panel).unwrap()[0];
+        let (panel, label) = (gui.layout_rect(panel), gui.layout_rect(label));
+        // Wrapped onto several lines, inside the panel.
+        assert!(label.bottom - label.top > 40., "{:?}", label);
+        assert!(label.right - label.left <= 328.);
+        assert!(label.bottom < panel.bottom);
+    }
+}
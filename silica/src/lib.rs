@@ -1,13 +1,15 @@
+mod dialog;
 mod render;
 pub mod view;
 
 use grist::{obj_upcast, Obj};
 use palette::LinSrgba;
 use std::collections::HashMap;
-use taffy::{prelude::*, Point};
+use taffy::{prelude::*, Overflow, Point};
 
-use view::{Control, View};
+use view::{scroll_view::ScrollView, Control, View};
 
+pub use dialog::*;
 pub use render::*;
 pub use taffy::{self, NodeId};
 
@@ -18,20 +20,71 @@ pub enum PointerState {
     Press,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Key {
+    Enter,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Backspace,
+    Delete,
+    Home,
+    End,
+}
+
+// The system cursors. Gui::cursor_icon picks one for the control under the pointer, which the game then sets.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CursorIcon {
+    #[default]
+    Default,
+    Help,
+    Pointer,
+    Wait,
+    Crosshair,
+    Text,
+    Move,
+    NotAllowed,
+    EWResize,
+    NSResize,
+    NESWResize,
+    NWSEResize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
 #[derive(Default)]
 pub struct GuiState {
     screen_size: Size<f32>,
+    dpi_scale: f32,
     pointer: Point<f32>,
     pointer_down: bool,
     highlight: Option<NodeId>,
+    // The control under the pointer, which can differ from the highlight after keyboard navigation.
+    hovered: Option<NodeId>,
+    // The control the pointer button was pressed on, which keeps getting pointer moves until it's released.
+    pressed: Option<NodeId>,
+    navigation_wrap: bool,
 }
 
 pub struct Gui {
     state: GuiState,
     layout: TaffyTree<()>,
     root: NodeId,
+    overlay: Option<NodeId>,
+    background: Option<LinSrgba>,
+    dialog: Option<(NodeId, Obj<Dialog>)>,
     views: HashMap<NodeId, Obj<dyn View>>,
     controls: HashMap<NodeId, Obj<dyn Control>>,
+    scroll_views: HashMap<NodeId, Obj<ScrollView>>,
+    text_measurer: Option<TextMeasurer>,
 }
 
 impl Gui {
@@ -39,16 +92,45 @@ impl Gui {
         let mut layout = TaffyTree::new();
         let root = layout.new_leaf(Style::DEFAULT).unwrap();
         Gui {
-            state: Default::default(),
+            state: GuiState {
+                dpi_scale: 1.,
+                ..Default::default()
+            },
             layout,
             root,
+            overlay: None,
+            background: None,
+            dialog: None,
             views: HashMap::new(),
             controls: HashMap::new(),
+            scroll_views: HashMap::new(),
+            text_measurer: None,
         }
     }
 
+    // Layout happens in logical pixels. Screen size and pointer positions are given in physical pixels, and are
+    // divided by the DPI scale.
     pub fn set_screen_size(&mut self, width: f32, height: f32) {
-        self.state.screen_size = Size { width, height };
+        self.state.screen_size = Size {
+            width: width / self.state.dpi_scale,
+            height: height / self.state.dpi_scale,
+        };
+        self.layout();
+    }
+    pub fn dpi_scale(&self) -> f32 {
+        self.state.dpi_scale
+    }
+    pub fn set_dpi_scale(&mut self, dpi_scale: f32) {
+        let screen_size = self.state.screen_size.map(|x| x * self.state.dpi_scale);
+        self.state.dpi_scale = dpi_scale;
+        self.set_screen_size(screen_size.width, screen_size.height);
+    }
+    // Without a text measurer, views without a size in their style (like labels) are laid out with zero size.
+    pub fn set_text_measurer(&mut self, text_measurer: TextMeasurer) {
+        self.text_measurer = Some(text_measurer);
+        for node in self.views.keys() {
+            self.layout.mark_dirty(*node).unwrap();
+        }
         self.layout();
     }
     pub fn root(&self) -> NodeId {
@@ -61,10 +143,26 @@ impl Gui {
         if self.root != root {
             self.root = root;
             self.layout();
-            self.handle_pointer_motion(self.state.pointer.x, self.state.pointer.y);
+            self.update_highlight();
+        }
+    }
+    pub fn overlay(&self) -> Option<NodeId> {
+        self.overlay
+    }
+    // The overlay is drawn on top of the root and receives all pointer input while it is set.
+    pub fn set_overlay(&mut self, overlay: Option<NodeId>) {
+        if self.overlay != overlay {
+            self.overlay = overlay;
+            self.layout();
+            self.update_highlight();
         }
     }
 
+    // Fills the screen behind the root. It isn't a node, so it never takes input.
+    pub fn set_background(&mut self, background: Option<LinSrgba>) {
+        self.background = background;
+    }
+
     pub fn add_child(&mut self, parent: NodeId, child: NodeId) {
         self.layout.add_child(parent, child).unwrap();
     }
@@ -73,6 +171,12 @@ impl Gui {
         if self.state.highlight == Some(child) {
             self.state.highlight = None;
         }
+        if self.state.hovered == Some(child) {
+            self.state.hovered = None;
+        }
+        if self.state.pressed == Some(child) {
+            self.state.pressed = None;
+        }
     }
 
     pub fn add_node(&mut self, parent: NodeId, style: Style) -> NodeId {
@@ -96,35 +200,319 @@ impl Gui {
         self.controls.insert(node, obj_upcast!(control).upgrade());
         node
     }
+    // Children of the node are clipped to it and scrolled by the mouse wheel. The content can scroll as far as it
+    // extends past the node, so the children need a size that doesn't shrink to fit (e.g. flex_shrink 0).
+    pub fn add_scroll_view(
+        &mut self,
+        parent: NodeId,
+        scroll_view: Obj<ScrollView>,
+        style: Style,
+    ) -> NodeId {
+        let node = self.add_node(parent, style);
+        self.scroll_views.insert(node, scroll_view);
+        node
+    }
+    pub fn view(&self, node: NodeId) -> Option<Obj<dyn View>> {
+        self.views.get(&node).cloned()
+    }
+    pub fn control(&self, node: NodeId) -> Option<Obj<dyn Control>> {
+        self.controls.get(&node).cloned()
+    }
     pub fn destroy(&mut self, node: NodeId) {
         self.layout.remove(node).unwrap();
         self.views.remove(&node);
         self.controls.remove(&node);
+        self.scroll_views.remove(&node);
         if self.state.highlight == Some(node) {
             self.state.highlight = None;
         }
+        if self.state.hovered == Some(node) {
+            self.state.hovered = None;
+        }
+        if self.state.pressed == Some(node) {
+            self.state.pressed = None;
+        }
+    }
+    pub fn destroy_tree(&mut self, node: NodeId) {
+        for child in self.layout.children(node).unwrap() {
+            self.destroy_tree(child);
+        }
+        self.destroy(node);
     }
 
     pub fn set_style(&mut self, node: NodeId, style: Style) {
         self.layout.set_style(node, style).unwrap();
     }
+    // The node's rect in logical pixels, relative to the top left of the screen.
+    pub fn layout_rect(&self, node: NodeId) -> Rect<f32> {
+        let layout = self.layout.layout(node).unwrap();
+        let mut position = layout.location;
+        let mut parent = self.layout.parent(node);
+        while let Some(node) = parent {
+            position = position + self.layout.layout(node).unwrap().location;
+            if let Some(scroll_view) = self.scroll_views.get(&node) {
+                let offset = scroll_view.get().scroll_offset();
+                position.x -= offset.x;
+                position.y -= offset.y;
+            }
+            parent = self.layout.parent(node);
+        }
+        Rect {
+            left: position.x,
+            right: position.x + layout.size.width,
+            top: position.y,
+            bottom: position.y + layout.size.height,
+        }
+    }
 
     pub fn render(&self, renderer: &mut dyn Renderer) {
-        let mut renderer = GuiRenderer::new(renderer);
+        let mut renderer = GuiRenderer::new(renderer, self.text_measurer.as_ref());
+        if let Some(background) = self.background {
+            renderer.set_size(self.state.screen_size);
+            renderer.set_color(background);
+            renderer.draw_rect();
+        }
         self.render_node(&mut renderer, self.root);
+        if let Some(overlay) = self.overlay {
+            self.render_node(&mut renderer, overlay);
+        }
     }
+    // Layout is cached, so this is cheap to call every frame. Views whose content changed are measured again, and a
+    // dialog resolved since the last call is closed.
     pub fn layout(&mut self) {
+        self.update_dialog();
+        for (node, view) in self.views.iter() {
+            if view.get_mut().take_layout_changed() {
+                self.layout.mark_dirty(*node).unwrap();
+            }
+        }
+        self.layout_root(self.root);
+        if let Some(overlay) = self.overlay {
+            self.layout_root(overlay);
+        }
+        for (node, scroll_view) in self.scroll_views.iter() {
+            let layout = self.layout.layout(*node).unwrap();
+            scroll_view.get_mut().set_max_scroll_offset(Point {
+                x: (layout.content_size.width - layout.size.width).max(0.),
+                y: (layout.content_size.height - layout.size.height).max(0.),
+            });
+        }
+    }
+    pub fn handle_pointer_motion(&mut self, x: f32, y: f32) {
+        let pointer = Point {
+            x: x / self.state.dpi_scale,
+            y: y / self.state.dpi_scale,
+        };
+        // Only a pointer that moved takes the highlight, so it doesn't undo keyboard navigation.
+        if pointer != self.state.pointer {
+            self.state.pointer = pointer;
+            self.update_highlight();
+        }
+    }
+    pub fn handle_pointer_button(&mut self, pressed: bool) {
+        if self.state.pointer_down == pressed {
+            return;
+        }
+        if let Some(node) = self.state.highlight {
+            if let Some(widget) = self.controls.get(&node) {
+                widget.get_mut().handle_pointer(if pressed {
+                    PointerState::Press
+                } else {
+                    PointerState::Over
+                });
+            }
+        }
+        if pressed {
+            self.state.pressed = self.state.hovered;
+        } else if let Some(control) = self
+            .state
+            .pressed
+            .take()
+            .and_then(|node| self.controls.get(&node))
+        {
+            control.get_mut().on_release();
+        }
+        self.state.pointer_down = pressed;
+        self.update_dialog();
+    }
+    // Scrolls the innermost scroll view under the pointer that can still move that way. The amounts are as miniquad
+    // reports them, with positive values scrolling up or left. Returns whether anything scrolled.
+    pub fn handle_pointer_wheel(&mut self, x: f32, y: f32) -> bool {
+        let mut scroll_views = Vec::new();
+        self.hit_scroll_views(
+            self.overlay.unwrap_or(self.root),
+            self.state.pointer.x,
+            self.state.pointer.y,
+            &mut scroll_views,
+        );
+        for node in scroll_views.into_iter().rev() {
+            if self.scroll_views[&node].get_mut().scroll_wheel(x, y) {
+                // The content moved under the pointer.
+                self.update_highlight();
+                return true;
+            }
+        }
+        false
+    }
+    // Keys go to the open dialog, or else the highlighted control. Held keys send repeats, which each control
+    // decides whether to honor: dialogs and buttons ignore them, so holding Enter only activates once.
+    pub fn handle_key(&mut self, key: Key, repeat: bool) -> bool {
+        if let Some((_, dialog)) = &self.dialog {
+            let handled = dialog.get_mut().handle_key(key, repeat);
+            self.update_dialog();
+            if handled {
+                return true;
+            }
+        } else if let Some(node) = self.state.highlight {
+            if let Some(control) = self.controls.get(&node) {
+                if control.get_mut().handle_key(key, repeat) {
+                    return true;
+                }
+            }
+        }
+        let direction = match key {
+            Key::Up => Direction::Up,
+            Key::Down => Direction::Down,
+            Key::Left => Direction::Left,
+            Key::Right => Direction::Right,
+            _ => return false,
+        };
+        self.navigate(direction);
+        true
+    }
+
+    // Typed text goes to the highlighted control. Returns whether it was used, like handle_key.
+    pub fn handle_char(&mut self, character: char) -> bool {
+        match self
+            .state
+            .highlight
+            .and_then(|node| self.controls.get(&node))
+        {
+            Some(control) => control.get_mut().handle_char(character),
+            None => false,
+        }
+    }
+
+    // The cursor for the control being dragged, or else the one under the pointer.
+    pub fn cursor_icon(&self) -> CursorIcon {
+        self.state
+            .pressed
+            .or(self.state.hovered)
+            .and_then(|node| self.controls.get(&node))
+            .map(|control| control.get().cursor_icon())
+            .unwrap_or_default()
+    }
+
+    // When navigating past the last control in a direction, whether to wrap around to the other side.
+    pub fn set_navigation_wrap(&mut self, wrap: bool) {
+        self.state.navigation_wrap = wrap;
+    }
+    // Moves the highlight to the nearest control in the direction, preferring controls in line with the current one
+    // (in the same row when moving left or right). Without a highlighted control, the first control is highlighted.
+    pub fn navigate(&mut self, direction: Direction) {
+        let mut controls = Vec::new();
+        self.collect_controls(self.overlay.unwrap_or(self.root), &mut controls);
+        let current = match self.state.highlight {
+            Some(node) if controls.contains(&node) => node,
+            _ => {
+                if let Some(first) = controls.first() {
+                    self.set_highlight(Some(*first));
+                }
+                return;
+            }
+        };
+        let from = self.layout_rect(current);
+        let candidates: Vec<(NodeId, Rect<f32>)> = controls
+            .into_iter()
+            .filter(|node| *node != current)
+            .map(|node| (node, self.layout_rect(node)))
+            .collect();
+        let mut target = Self::nearest_in_direction(from, direction, &candidates);
+        if target.is_none() && self.state.navigation_wrap {
+            // Wrap to the control furthest away in the opposite direction.
+            let opposite = match direction {
+                Direction::Up => Direction::Down,
+                Direction::Down => Direction::Up,
+                Direction::Left => Direction::Right,
+                Direction::Right => Direction::Left,
+            };
+            target = Self::furthest_in_direction(from, opposite, &candidates);
+        }
+        if let Some(target) = target {
+            self.scroll_into_view(target);
+            self.set_highlight(Some(target));
+        }
+    }
+    // Scrolls each scroll view the node is in just far enough to show it, e.g. after navigating to it.
+    pub fn scroll_into_view(&mut self, node: NodeId) {
+        let mut parent = self.layout.parent(node);
+        while let Some(ancestor) = parent {
+            if let Some(scroll_view) = self.scroll_views.get(&ancestor) {
+                let rect = self.layout_rect(node);
+                let visible = self.layout_rect(ancestor);
+                let scroll_by = |start: f32, end: f32, visible_start: f32, visible_end: f32| {
+                    if start < visible_start {
+                        start - visible_start
+                    } else if end > visible_end {
+                        // A node bigger than the scroll view shows its start.
+                        (end - visible_end).min(start - visible_start)
+                    } else {
+                        0.
+                    }
+                };
+                let mut offset = scroll_view.get().scroll_offset();
+                offset.x += scroll_by(rect.left, rect.right, visible.left, visible.right);
+                offset.y += scroll_by(rect.top, rect.bottom, visible.top, visible.bottom);
+                scroll_view.get_mut().scroll_to(offset);
+            }
+            parent = self.layout.parent(ancestor);
+        }
+    }
+
+    fn layout_root(&mut self, node: NodeId) {
         let screen_size = self.state.screen_size;
-        let mut root_style = self.layout.style(self.root).unwrap().clone();
-        root_style.size = screen_size.map(Dimension::Length);
-        self.layout.set_style(self.root, root_style).unwrap();
+        let mut root_style = self.layout.style(node).unwrap().clone();
+        if root_style.size != screen_size.map(Dimension::Length) {
+            root_style.size = screen_size.map(Dimension::Length);
+            self.layout.set_style(node, root_style).unwrap();
+        }
+        let views = &self.views;
+        let text_measurer = self.text_measurer.as_ref();
         self.layout
-            .compute_layout(self.root, screen_size.map(AvailableSpace::Definite))
+            .compute_layout_with_measure(
+                node,
+                screen_size.map(AvailableSpace::Definite),
+                |known_dimensions, available_space, node, _, _| match (
+                    views.get(&node),
+                    text_measurer,
+                ) {
+                    (Some(view), Some(text_measurer)) => {
+                        view.get()
+                            .measure(known_dimensions, available_space, text_measurer)
+                    }
+                    _ => known_dimensions.unwrap_or(Size::ZERO),
+                },
+            )
             .unwrap();
     }
-    pub fn handle_pointer_motion(&mut self, x: f32, y: f32) {
-        self.state.pointer = Point { x, y };
-        let highlight = self.hit_highlightable_node(self.root, x, y);
+
+    fn update_highlight(&mut self) {
+        let input_root = self.overlay.unwrap_or(self.root);
+        let highlight =
+            self.hit_highlightable_node(input_root, self.state.pointer.x, self.state.pointer.y);
+        if highlight != self.state.hovered {
+            if let Some(control) = self.state.hovered.and_then(|node| self.controls.get(&node)) {
+                control.get_mut().on_leave();
+            }
+            if let Some(control) = highlight.and_then(|node| self.controls.get(&node)) {
+                control.get_mut().on_enter();
+            }
+            self.state.hovered = highlight;
+        }
+        self.send_pointer_move();
+        self.set_highlight(highlight);
+    }
+    fn set_highlight(&mut self, highlight: Option<NodeId>) {
         if highlight != self.state.highlight {
             if let Some(node) = self.state.highlight {
                 if let Some(widget) = self.controls.get(&node) {
@@ -143,20 +531,116 @@ impl Gui {
             self.state.highlight = highlight;
         }
     }
-    pub fn handle_pointer_button(&mut self, pressed: bool) {
-        if self.state.pointer_down == pressed {
-            return;
+
+    fn send_pointer_move(&self) {
+        let pressed = self
+            .state
+            .pressed
+            .filter(|node| self.state.hovered != Some(*node));
+        for node in [self.state.hovered, pressed].into_iter().flatten() {
+            if let Some(control) = self.controls.get(&node) {
+                let rect = self.layout_rect(node);
+                let position = Point {
+                    x: self.state.pointer.x - rect.left,
+                    y: self.state.pointer.y - rect.top,
+                };
+                let size = Size {
+                    width: rect.right - rect.left,
+                    height: rect.bottom - rect.top,
+                };
+                control.get_mut().on_move(position, size);
+            }
         }
-        if let Some(node) = self.state.highlight {
-            if let Some(widget) = self.controls.get(&node) {
-                widget.get_mut().handle_pointer(if pressed {
-                    PointerState::Press
-                } else {
-                    PointerState::Over
-                });
+    }
+
+    fn collect_controls(&self, node: NodeId, controls: &mut Vec<NodeId>) {
+        if self.controls.contains_key(&node) {
+            controls.push(node);
+        }
+        for child in self.layout.child_ids(node) {
+            self.collect_controls(child, controls);
+        }
+    }
+    // Distance along the direction from one rect's center to another's, and whether they're in line (overlap on the
+    // other axis).
+    fn direction_offset(from: Rect<f32>, to: Rect<f32>, direction: Direction) -> (f32, f32, bool) {
+        let center = |rect: Rect<f32>| Point {
+            x: (rect.left + rect.right) / 2.,
+            y: (rect.top + rect.bottom) / 2.,
+        };
+        let (from_center, to_center) = (center(from), center(to));
+        let (along, across, in_line) = match direction {
+            Direction::Up | Direction::Down => (
+                to_center.y - from_center.y,
+                to_center.x - from_center.x,
+                to.left < from.right && from.left < to.right,
+            ),
+            Direction::Left | Direction::Right => (
+                to_center.x - from_center.x,
+                to_center.y - from_center.y,
+                to.top < from.bottom && from.top < to.bottom,
+            ),
+        };
+        let along = match direction {
+            Direction::Up | Direction::Left => -along,
+            Direction::Down | Direction::Right => along,
+        };
+        (along, across, in_line)
+    }
+    fn nearest_in_direction(
+        from: Rect<f32>,
+        direction: Direction,
+        candidates: &[(NodeId, Rect<f32>)],
+    ) -> Option<NodeId> {
+        let mut best: Option<(NodeId, bool, f32)> = None;
+        for (node, rect) in candidates {
+            let (along, across, in_line) = Self::direction_offset(from, *rect, direction);
+            if along <= 0. {
+                continue;
+            }
+            let distance = if in_line {
+                along
+            } else {
+                (along * along + across * across).sqrt()
+            };
+            let better = match best {
+                None => true,
+                Some((_, best_in_line, best_distance)) => {
+                    (in_line && !best_in_line)
+                        || (in_line == best_in_line && distance < best_distance)
+                }
+            };
+            if better {
+                best = Some((*node, in_line, distance));
             }
         }
-        self.state.pointer_down = pressed;
+        best.map(|(node, _, _)| node)
+    }
+    fn furthest_in_direction(
+        from: Rect<f32>,
+        direction: Direction,
+        candidates: &[(NodeId, Rect<f32>)],
+    ) -> Option<NodeId> {
+        let mut best: Option<(NodeId, bool, f32, f32)> = None;
+        for (node, rect) in candidates {
+            let (along, across, in_line) = Self::direction_offset(from, *rect, direction);
+            if along <= 0. {
+                continue;
+            }
+            let better = match best {
+                None => true,
+                Some((_, best_in_line, best_along, best_across)) => {
+                    (in_line && !best_in_line)
+                        || (in_line == best_in_line
+                            && (along > best_along
+                                || (along == best_along && across.abs() < best_across)))
+                }
+            };
+            if better {
+                best = Some((*node, in_line, along, across.abs()));
+            }
+        }
+        best.map(|(node, _, _, _)| node)
     }
 
     fn render_node(&self, renderer: &mut GuiRenderer, node: NodeId) {
@@ -164,15 +648,34 @@ impl Gui {
         renderer.push_translation();
         renderer.translate(layout.location.x, layout.location.y);
 
+        // Nodes with overflow other than visible in their style clip their view and children to the node, as scroll
+        // views always do.
+        let scroll_view = self.scroll_views.get(&node);
+        let overflow = self.layout.style(node).unwrap().overflow;
+        let clip = scroll_view.is_some()
+            || overflow.x != Overflow::Visible
+            || overflow.y != Overflow::Visible;
+        if clip {
+            renderer.set_size(layout.size);
+            renderer.push_clip();
+        }
+
         if let Some(view) = self.views.get(&node) {
             renderer.set_size(layout.size);
             renderer.set_color(LinSrgba::new(1., 1., 1., 1.));
             view.get().render(renderer);
         }
 
+        if let Some(scroll_view) = scroll_view {
+            let offset = scroll_view.get().scroll_offset();
+            renderer.translate(-offset.x, -offset.y);
+        }
         for child in self.layout.child_ids(node) {
             self.render_node(renderer, child);
         }
+        if clip {
+            renderer.pop_clip();
+        }
 
         renderer.pop_translation();
     }
@@ -182,8 +685,9 @@ impl Gui {
         x -= layout.location.x;
         y -= layout.location.y;
         if x >= 0.0 && y >= 0.0 && x < layout.size.width && y < layout.size.height {
+            let (child_x, child_y) = self.scrolled_position(node, x, y);
             for child in self.layout.children(node).unwrap().into_iter().rev() {
-                if let Some(hit_node) = self.hit_highlightable_node(child, x, y) {
+                if let Some(hit_node) = self.hit_highlightable_node(child, child_x, child_y) {
                     return Some(hit_node);
                 }
             }
@@ -193,4 +697,165 @@ impl Gui {
         }
         None
     }
+    // Outer scroll views come before the ones inside them.
+    fn hit_scroll_views(&self, node: NodeId, mut x: f32, mut y: f32, hits: &mut Vec<NodeId>) {
+        let layout = self.layout.layout(node).unwrap();
+        x -= layout.location.x;
+        y -= layout.location.y;
+        if x >= 0.0 && y >= 0.0 && x < layout.size.width && y < layout.size.height {
+            if self.scroll_views.contains_key(&node) {
+                hits.push(node);
+            }
+            let (x, y) = self.scrolled_position(node, x, y);
+            for child in self.layout.child_ids(node) {
+                self.hit_scroll_views(child, x, y, hits);
+            }
+        }
+    }
+    // A position in the node, moved into its children's space if it's a scroll view.
+    fn scrolled_position(&self, node: NodeId, x: f32, y: f32) -> (f32, f32) {
+        match self.scroll_views.get(&node) {
+            Some(scroll_view) => {
+                let offset = scroll_view.get().scroll_offset();
+                (x + offset.x, y + offset.y)
+            }
+            None => (x, y),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    // Records what the Gui sends it.
+    struct Probe {
+        name: &'static str,
+        log: Log,
+    }
+
+    impl View for Probe {
+        fn render(&self, _renderer: &mut GuiRenderer) {}
+    }
+    impl Control for Probe {
+        fn handle_pointer(&mut self, state: PointerState) {
+            self.log
+                .borrow_mut()
+                .push(format!("{} {:?}", self.name, state));
+        }
+        fn on_enter(&mut self) {
+            self.log.borrow_mut().push(format!("{} enter", self.name));
+        }
+        fn on_leave(&mut self) {
+            self.log.borrow_mut().push(format!("{} leave", self.name));
+        }
+        fn on_move(&mut self, position: Point<f32>, _size: Size<f32>) {
+            self.log
+                .borrow_mut()
+                .push(format!("{} move {},{}", self.name, position.x, position.y));
+        }
+        fn on_release(&mut self) {
+            self.log.borrow_mut().push(format!("{} release", self.name));
+        }
+    }
+
+    // A 2x2 grid of 100x100 controls, in reading order.
+    fn grid(log: &Log) -> (Gui, [NodeId; 4]) {
+        let mut gui = Gui::new();
+        gui.set_style(
+            gui.root(),
+            Style {
+                flex_wrap: FlexWrap::Wrap,
+                ..Default::default()
+            },
+        );
+        let nodes = ["a", "b", "c", "d"].map(|name| {
+            let probe = Obj::new(Probe {
+                name,
+                log: log.clone(),
+            });
+            gui.add_view_control(
+                gui.root(),
+                probe,
+                Style {
+                    size: Size::from_lengths(100., 100.),
+                    ..Default::default()
+                },
+            )
+        });
+        gui.set_screen_size(200., 200.);
+        (gui, nodes)
+    }
+
+    #[test]
+    fn navigate_grid() {
+        let (mut gui, [a, b, c, d]) = grid(&Log::default());
+        gui.navigate(Direction::Right);
+        assert_eq!(gui.state.highlight, Some(a));
+        for (direction, expected) in [
+            (Direction::Right, b),
+            (Direction::Right, b),
+            (Direction::Down, d),
+            (Direction::Down, d),
+            (Direction::Left, c),
+            (Direction::Up, a),
+        ] {
+            gui.navigate(direction);
+            assert_eq!(gui.state.highlight, Some(expected), "{:?}", direction);
+        }
+    }
+
+    #[test]
+    fn navigate_grid_wrap() {
+        let (mut gui, [a, b, c, d]) = grid(&Log::default());
+        gui.set_navigation_wrap(true);
+        gui.navigate(Direction::Down);
+        for (direction, expected) in [
+            (Direction::Left, b),
+            (Direction::Left, a),
+            (Direction::Up, c),
+            (Direction::Right, d),
+            (Direction::Down, b),
+        ] {
+            gui.navigate(direction);
+            assert_eq!(gui.state.highlight, Some(expected), "{:?}", direction);
+        }
+    }
+
+    #[test]
+    fn pointer_event_order() {
+        let log = Log::default();
+        let (mut gui, _) = grid(&log);
+        let expect = |expected: &[&str]| {
+            assert_eq!(*log.borrow(), expected);
+            log.borrow_mut().clear();
+        };
+
+        gui.handle_pointer_motion(10., 20.);
+        expect(&["a enter", "a move 10,20", "a Over"]);
+        gui.handle_pointer_motion(20., 20.);
+        expect(&["a move 20,20"]);
+        gui.handle_pointer_motion(150., 20.);
+        expect(&["a leave", "b enter", "b move 50,20", "a None", "b Over"]);
+
+        // Dragging from b onto a keeps sending b moves until the release.
+        gui.handle_pointer_button(true);
+        expect(&["b Press"]);
+        gui.handle_pointer_motion(50., 50.);
+        expect(&[
+            "b leave",
+            "a enter",
+            "a move 50,50",
+            "b move -50,50",
+            "b None",
+            "a Press",
+        ]);
+        gui.handle_pointer_button(false);
+        expect(&["a Over", "b release"]);
+        gui.handle_pointer_motion(60., 50.);
+        expect(&["a move 60,50"]);
+    }
 }
@@ -1,5 +1,9 @@
-use glyph_brush::{ab_glyph::PxScale, Extra, Section};
+use glyph_brush::{
+    ab_glyph::{Font, FontArc, PxScale},
+    Extra, GlyphCalculator, GlyphCalculatorBuilder, GlyphCruncher, Section,
+};
 use palette::LinSrgba;
+use std::any::Any;
 use taffy::{geometry::Point, prelude::*};
 
 use crate::PointerState;
@@ -13,6 +17,10 @@ pub struct Text {
     pub text: String,
     pub h_align: HorizontalAlign,
     pub v_align: VerticalAlign,
+    // Breaks lines at the width the text is drawn or measured in. Text with line breaks in it always wraps.
+    pub wrap: bool,
+    // Drawn after text, on the same lines, for mixing fonts, sizes or colors.
+    pub runs: Vec<TextRun>,
 }
 
 impl Default for Text {
@@ -23,6 +31,86 @@ impl Default for Text {
             text: String::new(),
             h_align: HorizontalAlign::Left,
             v_align: VerticalAlign::Center,
+            wrap: false,
+            runs: Vec::new(),
+        }
+    }
+}
+
+// Extra text drawn after a Text, in its own font and color. Runs share the alignment of the Text, and its size unless
+// they set their own.
+#[derive(Debug, Clone)]
+pub struct TextRun {
+    pub text: String,
+    pub font: FontId,
+    pub font_size: Option<f32>,
+    pub color: LinSrgba,
+}
+
+impl TextRun {
+    pub fn new(text: &str, font: FontId, color: LinSrgba) -> Self {
+        TextRun {
+            text: text.to_owned(),
+            font,
+            font_size: None,
+            color,
+        }
+    }
+}
+
+fn text_layout(text: &Text) -> glyph_brush::Layout<glyph_brush::BuiltInLineBreaker> {
+    let layout = if text.wrap
+        || text.text.contains('\n')
+        || text.runs.iter().any(|run| run.text.contains('\n'))
+    {
+        glyph_brush::Layout::default_wrap()
+    } else {
+        glyph_brush::Layout::default_single_line()
+    };
+    layout.h_align(text.h_align).v_align(text.v_align)
+}
+
+// Measures text in logical pixels, for sizing views to their content during layout.
+pub struct TextMeasurer {
+    glyphs: GlyphCalculator,
+}
+
+impl TextMeasurer {
+    pub fn new(fonts: Vec<FontArc>) -> Self {
+        TextMeasurer {
+            glyphs: GlyphCalculatorBuilder::using_fonts(fonts).build(),
+        }
+    }
+
+    pub fn measure(&self, text: &Text, max_width: Option<f32>) -> Size<f32> {
+        let scale = |font: FontId, font_size: f32| {
+            let font = self.glyphs.fonts().get(font.0).expect("invalid FontId");
+            font.pt_to_px_scale(font_size).unwrap()
+        };
+        let mut glyph_texts = vec![glyph_brush::Text::new(&text.text)
+            .with_scale(scale(text.font, text.font_size))
+            .with_font_id(text.font)];
+        for run in &text.runs {
+            glyph_texts.push(
+                glyph_brush::Text::new(&run.text)
+                    .with_scale(scale(run.font, run.font_size.unwrap_or(text.font_size)))
+                    .with_font_id(run.font),
+            );
+        }
+        let section = Section::default()
+            .with_text(glyph_texts)
+            .with_layout(
+                text_layout(text)
+                    .h_align(HorizontalAlign::Left)
+                    .v_align(VerticalAlign::Top),
+            )
+            .with_bounds((max_width.unwrap_or(f32::INFINITY), f32::INFINITY));
+        match self.glyphs.cache_scope().glyph_bounds(&section) {
+            Some(bounds) => Size {
+                width: bounds.width().ceil(),
+                height: bounds.height().ceil(),
+            },
+            None => Size::ZERO,
         }
     }
 }
@@ -30,6 +118,19 @@ impl Default for Text {
 pub trait Renderer {
     fn queue_rect(&mut self, point: Point<f32>, size: Size<f32>, color: LinSrgba);
     fn queue_text(&mut self, section: Section);
+    // Draws the part of the texture within uv (from 0 to 1 across the texture), tinted by the color. The texture is
+    // whatever the renderer draws with, e.g. a gristmill::Texture for QuadRenderer.
+    fn queue_texture(
+        &mut self,
+        texture: &dyn Any,
+        point: Point<f32>,
+        size: Size<f32>,
+        uv: Rect<f32>,
+        color: LinSrgba,
+    );
+    // Clips what's queued until the matching pop_clip to the rect, within any clip already pushed.
+    fn push_clip(&mut self, point: Point<f32>, size: Size<f32>);
+    fn pop_clip(&mut self);
     fn pt_to_px_scale(&self, font: FontId, pt_size: f32) -> PxScale;
 }
 
@@ -105,19 +206,26 @@ pub struct GuiRenderer<'a> {
     translation_stack: Vec<Point<f32>>,
     size: Size<f32>,
     color: LinSrgba,
+    measurer: Option<&'a TextMeasurer>,
 }
 
 impl<'a> GuiRenderer<'a> {
-    pub(crate) fn new(renderer: &'a mut dyn Renderer) -> Self {
+    pub(crate) fn new(renderer: &'a mut dyn Renderer, measurer: Option<&'a TextMeasurer>) -> Self {
         GuiRenderer {
             renderer,
             translation: Point::ZERO,
             translation_stack: Vec::new(),
             size: Size::ZERO,
             color: Default::default(),
+            measurer,
         }
     }
 
+    // The Gui's text measurer, for views that position things relative to their text. None until the Gui has one.
+    pub fn measurer(&self) -> Option<&TextMeasurer> {
+        self.measurer
+    }
+
     pub fn push_translation(&mut self) {
         self.translation_stack.push(self.translation);
     }
@@ -138,6 +246,18 @@ impl<'a> GuiRenderer<'a> {
         self.size = size;
     }
 
+    // Nothing drawn outside of the node (or the given area of it) shows until pop_clip. Clips nest, so a clip inside
+    // another only shows the part inside both.
+    pub fn push_clip(&mut self) {
+        self.renderer.push_clip(self.translation, self.size);
+    }
+    pub fn push_clip_at(&mut self, point: Point<f32>, size: Size<f32>) {
+        self.renderer.push_clip(self.translation + point, size);
+    }
+    pub fn pop_clip(&mut self) {
+        self.renderer.pop_clip();
+    }
+
     pub fn set_color(&mut self, color: LinSrgba) {
         self.color = color;
     }
@@ -175,28 +295,133 @@ impl<'a> GuiRenderer<'a> {
         self.renderer
             .queue_rect(self.translation + point, size, self.color);
     }
+    // Textures are tinted by the color, so set it to white to draw them unchanged.
+    pub fn draw_texture(&mut self, texture: &dyn Any, uv: Rect<f32>) {
+        self.draw_texture_at(texture, Point::ZERO, self.size, uv);
+    }
+    pub fn draw_texture_at(
+        &mut self,
+        texture: &dyn Any,
+        point: Point<f32>,
+        size: Size<f32>,
+        uv: Rect<f32>,
+    ) {
+        self.renderer
+            .queue_texture(texture, self.translation + point, size, uv, self.color);
+    }
+    // Fills the node with the texture, keeping the corners (the insets, in the texture's pixels) the same size and
+    // stretching the edges and center between them. The corners shrink if the node is too small to fit them.
+    pub fn draw_nine_slice(
+        &mut self,
+        texture: &dyn Any,
+        texture_size: Size<f32>,
+        insets: Rect<f32>,
+    ) {
+        let size = self.size;
+        let fit = |start: f32, end: f32, length: f32| {
+            if start + end > length && start + end > 0. {
+                let scale = length / (start + end);
+                (start * scale, end * scale)
+            } else {
+                (start, end)
+            }
+        };
+        let (left, right) = fit(insets.left, insets.right, size.width);
+        let (top, bottom) = fit(insets.top, insets.bottom, size.height);
+        let xs = [0., left, size.width - right, size.width];
+        let ys = [0., top, size.height - bottom, size.height];
+        let us = [
+            0.,
+            insets.left / texture_size.width,
+            1. - insets.right / texture_size.width,
+            1.,
+        ];
+        let vs = [
+            0.,
+            insets.top / texture_size.height,
+            1. - insets.bottom / texture_size.height,
+            1.,
+        ];
+        for row in 0..3 {
+            for column in 0..3 {
+                let point = Point {
+                    x: xs[column],
+                    y: ys[row],
+                };
+                let slice_size = Size {
+                    width: xs[column + 1] - xs[column],
+                    height: ys[row + 1] - ys[row],
+                };
+                if slice_size.width <= 0. || slice_size.height <= 0. {
+                    continue;
+                }
+                let uv = Rect {
+                    left: us[column],
+                    right: us[column + 1],
+                    top: vs[row],
+                    bottom: vs[row + 1],
+                };
+                self.draw_texture_at(texture, point, slice_size, uv);
+            }
+        }
+    }
+    // Filled, from a thin rect for each logical pixel row.
+    pub fn draw_circle_at(&mut self, center: Point<f32>, radius: f32) {
+        let rows = (radius * 2.).ceil().max(1.);
+        let row_height = radius * 2. / rows;
+        for row in 0..rows as usize {
+            let top = row as f32 * row_height - radius;
+            let middle = top + row_height / 2.;
+            let half_width = (radius * radius - middle * middle).max(0.).sqrt();
+            self.draw_rect_at(
+                Point {
+                    x: center.x - half_width,
+                    y: center.y + top,
+                },
+                Size {
+                    width: half_width * 2.,
+                    height: row_height,
+                },
+            );
+        }
+    }
     pub fn draw_text(&mut self, text: &Text) {
-        let mut layout = if text.text.contains('\n') {
-            glyph_brush::Layout::default_wrap()
-        } else {
-            glyph_brush::Layout::default_single_line()
+        let rect = Rect {
+            left: 0.,
+            right: self.size.width,
+            top: 0.,
+            bottom: self.size.height,
+        };
+        self.draw_text_in(rect, text);
+    }
+    // Aligns text within the rect (in the node's coordinates), e.g. centered text is centered between its left and
+    // right, and bottom aligned text sits on its bottom.
+    pub fn draw_text_in(&mut self, rect: Rect<f32>, text: &Text) {
+        let layout = text_layout(text);
+        let origin = self.translation
+            + Point {
+                x: rect.left,
+                y: rect.top,
+            };
+        let size = Size {
+            width: (rect.right - rect.left).max(0.),
+            height: (rect.bottom - rect.top).max(0.),
         };
-        layout = layout.h_align(text.h_align).v_align(text.v_align);
         let screen_position = (
-            self.translation.x
+            origin.x
                 + match text.h_align {
                     HorizontalAlign::Left => 0.,
-                    HorizontalAlign::Center => self.size.width / 2.,
-                    HorizontalAlign::Right => self.size.width,
+                    HorizontalAlign::Center => size.width / 2.,
+                    HorizontalAlign::Right => size.width,
                 },
-            self.translation.y
+            origin.y
                 + match text.v_align {
                     VerticalAlign::Top => 0.,
-                    VerticalAlign::Center => self.size.height / 2.,
-                    VerticalAlign::Bottom => self.size.height,
+                    VerticalAlign::Center => size.height / 2.,
+                    VerticalAlign::Bottom => size.height,
                 },
         );
-        let text = glyph_brush::Text {
+        let mut glyph_texts = vec![glyph_brush::Text {
             text: &text.text,
             scale: self.renderer.pt_to_px_scale(text.font, text.font_size),
             font_id: text.font,
@@ -204,13 +429,26 @@ impl<'a> GuiRenderer<'a> {
                 color: self.color.into(),
                 z: 0.,
             },
-        };
-        let bounds = (self.size.width, self.size.height);
+        }];
+        for run in &text.runs {
+            glyph_texts.push(glyph_brush::Text {
+                text: &run.text,
+                scale: self
+                    .renderer
+                    .pt_to_px_scale(run.font, run.font_size.unwrap_or(text.font_size)),
+                font_id: run.font,
+                extra: Extra {
+                    color: run.color.into(),
+                    z: 0.,
+                },
+            });
+        }
+        let bounds = (size.width, size.height);
         self.renderer.queue_text(Section {
             screen_position,
             bounds,
             layout,
-            text: vec![text],
+            text: glyph_texts,
         });
     }
 }
@@ -220,6 +458,7 @@ pub struct SimpleColors {
     pub bg_hover: LinSrgba,
     pub bg_press: LinSrgba,
     pub bg_disable: LinSrgba,
+    pub bg_select: LinSrgba,
     pub fg_normal: LinSrgba,
     pub fg_disable: LinSrgba,
 }
@@ -229,6 +468,7 @@ impl SimpleColors {
     pub const BG_HOVER: LinSrgba = LinSrgba::new(0.278, 0.278, 0.278, 1.0);
     pub const BG_PRESS: LinSrgba = LinSrgba::new(0.341, 0.341, 0.341, 1.0);
     pub const BG_DISABLE: LinSrgba = LinSrgba::new(0.216, 0.216, 0.216, 0.5);
+    pub const BG_SELECT: LinSrgba = LinSrgba::new(0.153, 0.353, 0.549, 1.0);
     pub const FG_NORMAL: LinSrgba = LinSrgba::new(0.906, 0.906, 0.906, 1.0);
     pub const FG_DISABLE: LinSrgba = LinSrgba::new(0.906, 0.906, 0.906, 0.5);
 
@@ -259,8 +499,98 @@ impl Default for SimpleColors {
             bg_hover: Self::BG_HOVER,
             bg_press: Self::BG_PRESS,
             bg_disable: Self::BG_DISABLE,
+            bg_select: Self::BG_SELECT,
             fg_normal: Self::FG_NORMAL,
             fg_disable: Self::FG_DISABLE,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Keeps where text sections were queued.
+    #[derive(Default)]
+    struct TextPositions(Vec<((f32, f32), (f32, f32))>);
+
+    impl Renderer for TextPositions {
+        fn queue_rect(&mut self, _point: Point<f32>, _size: Size<f32>, _color: LinSrgba) {}
+        fn queue_text(&mut self, section: Section) {
+            self.0.push((section.screen_position, section.bounds));
+        }
+        fn queue_texture(
+            &mut self,
+            _texture: &dyn Any,
+            _point: Point<f32>,
+            _size: Size<f32>,
+            _uv: Rect<f32>,
+            _color: LinSrgba,
+        ) {
+        }
+        fn push_clip(&mut self, _point: Point<f32>, _size: Size<f32>) {}
+        fn pop_clip(&mut self) {}
+        fn pt_to_px_scale(&self, _font: FontId, pt_size: f32) -> PxScale {
+            PxScale::from(pt_size)
+        }
+    }
+
+    #[test]
+    fn text_aligned_in_rect() {
+        let mut positions = TextPositions::default();
+        let mut renderer = GuiRenderer::new(&mut positions, None);
+        renderer.translate(100., 50.);
+        renderer.set_size(Size {
+            width: 200.,
+            height: 40.,
+        });
+        let rect = Rect {
+            left: 20.,
+            right: 80.,
+            top: 10.,
+            bottom: 30.,
+        };
+        let mut text = Text {
+            h_align: HorizontalAlign::Center,
+            ..Default::default()
+        };
+        renderer.draw_text_in(rect, &text);
+        text.h_align = HorizontalAlign::Right;
+        text.v_align = VerticalAlign::Bottom;
+        renderer.draw_text_in(rect, &text);
+        renderer.draw_text(&text);
+        assert_eq!(
+            positions.0,
+            [
+                ((150., 70.), (60., 20.)),
+                ((180., 80.), (60., 20.)),
+                ((300., 90.), (200., 40.)),
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_are_measured() {
+        let font =
+            FontArc::try_from_slice(include_bytes!("../../gristmill/fonts/OpenSans-Regular.ttf"))
+                .unwrap();
+        let measurer = TextMeasurer::new(vec![font]);
+        let mut text = Text {
+            text: "Score: ".to_owned(),
+            ..Default::default()
+        };
+        let base = measurer.measure(&text, None);
+        text.runs.push(TextRun::new(
+            "100",
+            FontId(0),
+            LinSrgba::new(1., 1., 1., 1.),
+        ));
+        let with_run = measurer.measure(&text, None);
+        assert!(with_run.width > base.width, "{:?} {:?}", base, with_run);
+
+        // A run with its own size makes the line taller.
+        text.runs[0].font_size = Some(text.font_size * 2.);
+        let large_run = measurer.measure(&text, None);
+        assert!(large_run.width > with_run.width && large_run.height > with_run.height);
+    }
+}
@@ -2,7 +2,7 @@ use glyph_brush::{HorizontalAlign, VerticalAlign};
 use grist::{impl_add_event_listener, Event};
 use taffy::Rect;
 
-use crate::{Control, GuiRenderer, PointerState, SimpleColors, Text, View};
+use crate::{Control, CursorIcon, GuiRenderer, Key, PointerState, SimpleColors, Text, View};
 
 pub struct ButtonModel {
     pub enabled: bool,
@@ -90,9 +90,22 @@ impl Button {
     pub fn set_enabled(&mut self, enabled: bool) {
         self.model.enabled = enabled;
     }
+
+    fn activate(&mut self) {
+        if let Some(toggle) = self.model.toggle.as_mut() {
+            *toggle = !*toggle;
+        }
+        self.pressed.emit(&());
+    }
 }
 
-impl_add_event_listener!(Button, pressed, (), add_pressed_listener);
+impl_add_event_listener!(
+    Button,
+    pressed,
+    (),
+    add_pressed_listener,
+    remove_pressed_listener
+);
 
 impl View for Button {
     fn render(&self, renderer: &mut GuiRenderer) {
@@ -106,11 +119,24 @@ impl Control for Button {
             && self.model.state == PointerState::Over
             && state == PointerState::Press
         {
-            if let Some(toggle) = self.model.toggle.as_mut() {
-                *toggle = !*toggle;
-            }
-            self.pressed.emit(&());
+            self.activate();
         }
         self.model.state = state;
     }
+    fn handle_key(&mut self, key: Key, repeat: bool) -> bool {
+        if key == Key::Enter && self.model.enabled {
+            if !repeat {
+                self.activate();
+            }
+            return true;
+        }
+        false
+    }
+    fn cursor_icon(&self) -> CursorIcon {
+        if self.model.enabled {
+            CursorIcon::Pointer
+        } else {
+            CursorIcon::Default
+        }
+    }
 }
@@ -1,7 +1,7 @@
 use grist::{impl_add_event_listener, Event};
 use taffy::{Point, Rect, Size};
 
-use crate::{Control, GuiRenderer, PointerState, SimpleColors, View};
+use crate::{Control, CursorIcon, GuiRenderer, PointerState, SimpleColors, View};
 
 pub struct CheckboxModel {
     pub enabled: bool,
@@ -128,7 +128,13 @@ impl Checkbox {
     }
 }
 
-impl_add_event_listener!(Checkbox, changed, bool, add_changed_listener);
+impl_add_event_listener!(
+    Checkbox,
+    changed,
+    bool,
+    add_changed_listener,
+    remove_changed_listener
+);
 
 impl View for Checkbox {
     fn render(&self, renderer: &mut GuiRenderer) {
@@ -147,6 +153,13 @@ impl Control for Checkbox {
         }
         self.model.state = state;
     }
+    fn cursor_icon(&self) -> CursorIcon {
+        if self.model.enabled {
+            CursorIcon::Pointer
+        } else {
+            CursorIcon::Default
+        }
+    }
 }
 
 // impl Widget for Checkbox {
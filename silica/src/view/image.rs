@@ -0,0 +1,108 @@
+use palette::LinSrgba;
+use std::any::Any;
+use taffy::{prelude::*, Point};
+
+use crate::{GuiRenderer, TextMeasurer, View};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ImageScaling {
+    // Fills the node, changing the aspect ratio if the node's is different.
+    #[default]
+    Stretch,
+    // As big as fits in the node while keeping the aspect ratio, centered.
+    Fit,
+}
+
+// Draws a texture, of whatever type the Renderer draws with (e.g. gristmill::Texture). The size is the texture's size
+// in pixels, which is also the node's size if its style doesn't set one (and the Gui has a text measurer, which
+// measuring any view needs).
+pub struct Image<T> {
+    texture: T,
+    texture_size: Size<f32>,
+    uv_rect: Option<Rect<f32>>,
+    tint: Option<LinSrgba>,
+    scaling: ImageScaling,
+    layout_changed: bool,
+}
+
+impl<T: Any> Image<T> {
+    pub fn new(texture: T, texture_size: Size<f32>) -> Self {
+        Image {
+            texture,
+            texture_size,
+            uv_rect: None,
+            tint: None,
+            scaling: ImageScaling::Stretch,
+            layout_changed: false,
+        }
+    }
+
+    pub fn texture(&self) -> &T {
+        &self.texture
+    }
+    pub fn set_texture(&mut self, texture: T, texture_size: Size<f32>) {
+        self.texture = texture;
+        self.texture_size = texture_size;
+        self.layout_changed = true;
+    }
+    // Only draws the part of the texture within the rect (from 0 to 1 across the texture), e.g. an icon in an atlas.
+    pub fn set_uv_rect(&mut self, uv_rect: Option<Rect<f32>>) {
+        self.uv_rect = uv_rect;
+        self.layout_changed = true;
+    }
+    pub fn set_tint(&mut self, tint: Option<LinSrgba>) {
+        self.tint = tint;
+    }
+    pub fn set_scaling(&mut self, scaling: ImageScaling) {
+        self.scaling = scaling;
+    }
+
+    fn uv(&self) -> Rect<f32> {
+        self.uv_rect.unwrap_or(Rect {
+            left: 0.,
+            right: 1.,
+            top: 0.,
+            bottom: 1.,
+        })
+    }
+    // The size of the part of the texture that's drawn.
+    fn image_size(&self) -> Size<f32> {
+        let uv = self.uv();
+        Size {
+            width: self.texture_size.width * (uv.right - uv.left).abs(),
+            height: self.texture_size.height * (uv.bottom - uv.top).abs(),
+        }
+    }
+}
+
+impl<T: Any> View for Image<T> {
+    fn render(&self, renderer: &mut GuiRenderer) {
+        renderer.set_color(self.tint.unwrap_or(LinSrgba::new(1., 1., 1., 1.)));
+        let size = renderer.size();
+        let image_size = self.image_size();
+        match self.scaling {
+            ImageScaling::Stretch => renderer.draw_texture(&self.texture, self.uv()),
+            ImageScaling::Fit if image_size.width > 0. && image_size.height > 0. => {
+                let scale = (size.width / image_size.width).min(size.height / image_size.height);
+                let fit_size = image_size.map(|x| x * scale);
+                let point = Point {
+                    x: (size.width - fit_size.width) / 2.,
+                    y: (size.height - fit_size.height) / 2.,
+                };
+                renderer.draw_texture_at(&self.texture, point, fit_size, self.uv());
+            }
+            ImageScaling::Fit => {}
+        }
+    }
+    fn measure(
+        &self,
+        known_dimensions: Size<Option<f32>>,
+        _available_space: Size<AvailableSpace>,
+        _measurer: &TextMeasurer,
+    ) -> Size<f32> {
+        known_dimensions.unwrap_or(self.image_size())
+    }
+    fn take_layout_changed(&mut self) -> bool {
+        std::mem::take(&mut self.layout_changed)
+    }
+}
@@ -1,11 +1,16 @@
 use glyph_brush::FontId;
 use palette::LinSrgba;
+use std::cell::Cell;
+use taffy::prelude::*;
 
-use crate::{GuiRenderer, HorizontalAlign, SimpleColors, Text, VerticalAlign, View};
+use crate::{
+    GuiRenderer, HorizontalAlign, SimpleColors, Text, TextMeasurer, TextRun, VerticalAlign, View,
+};
 
 pub struct Label {
     text: Text,
     color: LinSrgba,
+    layout_changed: bool,
 }
 
 impl View for Label {
@@ -13,6 +18,21 @@ impl View for Label {
         renderer.set_color(self.color);
         renderer.draw_text(&self.text);
     }
+    fn measure(
+        &self,
+        known_dimensions: Size<Option<f32>>,
+        available_space: Size<AvailableSpace>,
+        measurer: &TextMeasurer,
+    ) -> Size<f32> {
+        let max_width = known_dimensions.width.or(match available_space.width {
+            AvailableSpace::Definite(width) => Some(width),
+            _ => None,
+        });
+        known_dimensions.unwrap_or(measurer.measure(&self.text, max_width))
+    }
+    fn take_layout_changed(&mut self) -> bool {
+        std::mem::take(&mut self.layout_changed)
+    }
 }
 
 impl Label {
@@ -26,20 +46,176 @@ impl Label {
                 ..Default::default()
             },
             color: SimpleColors::FG_NORMAL,
+            layout_changed: false,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text.text
+    }
+    pub fn set_text(&mut self, string: String) {
+        self.text.text = string;
+        self.layout_changed = true;
+    }
+    // Adds text after the label's text (and any earlier runs), in a different font or color. E.g. an icon font glyph
+    // followed by a number. set_text only changes the label's own text, not the appended runs.
+    pub fn append(&mut self, text: &str, font: FontId, color: LinSrgba) {
+        self.append_run(TextRun::new(text, font, color));
+    }
+    // Like append, but the run can also have its own font size.
+    pub fn append_run(&mut self, run: TextRun) {
+        self.text.runs.push(run);
+        self.layout_changed = true;
+    }
+    pub fn clear_appended(&mut self) {
+        self.text.runs.clear();
+        self.layout_changed = true;
+    }
+    pub fn set_font(&mut self, font: FontId) {
+        self.text.font = font;
+        self.layout_changed = true;
+    }
+    pub fn set_font_size(&mut self, font_size: f32) {
+        self.text.font_size = font_size;
+        self.layout_changed = true;
+    }
+    pub fn set_halign(&mut self, h_align: HorizontalAlign) {
+        self.text.h_align = h_align;
+    }
+    pub fn set_valign(&mut self, v_align: VerticalAlign) {
+        self.text.v_align = v_align;
+    }
+    // Wraps the text to the label's width, so a style with a fixed width and auto height fits long text.
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.text.wrap = wrap;
+        self.layout_changed = true;
+    }
+    pub fn set_color(&mut self, color: LinSrgba) {
+        self.color = color;
+    }
+}
+
+// A label for numbers that change often, like scores and timers. Each character is drawn separately in a cell as
+// wide as a digit, so glyph_brush can reuse the layout of every digit that didn't change, instead of laying out the
+// whole text again each frame. The digit width is measured during layout, so this needs a TextMeasurer on the Gui
+// and a style without an explicit width; otherwise, and for text that isn't numeric, it draws like a Label.
+pub struct NumberLabel {
+    text: Text,
+    color: LinSrgba,
+    digit_size: Cell<Option<Size<f32>>>,
+    layout_changed: bool,
+}
+
+impl View for NumberLabel {
+    fn render(&self, renderer: &mut GuiRenderer) {
+        renderer.set_color(self.color);
+        let digit_size = match self.digit_size.get() {
+            Some(digit_size) if Self::is_numeric(&self.text.text) => digit_size,
+            _ => {
+                renderer.draw_text(&self.text);
+                return;
+            }
+        };
+        let size = renderer.size();
+        let width = digit_size.width * self.text.text.chars().count() as f32;
+        let mut x = match self.text.h_align {
+            HorizontalAlign::Left => 0.,
+            HorizontalAlign::Center => (size.width - width) / 2.,
+            HorizontalAlign::Right => size.width - width,
+        };
+        let mut cell = Text {
+            h_align: HorizontalAlign::Center,
+            runs: Vec::new(),
+            ..self.text.clone()
+        };
+        let mut buffer = [0; 4];
+        for c in self.text.text.chars() {
+            cell.text.clear();
+            cell.text.push_str(c.encode_utf8(&mut buffer));
+            let rect = Rect {
+                left: x,
+                right: x + digit_size.width,
+                top: 0.,
+                bottom: size.height,
+            };
+            renderer.draw_text_in(rect, &cell);
+            x += digit_size.width;
+        }
+    }
+    fn measure(
+        &self,
+        known_dimensions: Size<Option<f32>>,
+        _available_space: Size<AvailableSpace>,
+        measurer: &TextMeasurer,
+    ) -> Size<f32> {
+        let mut widest = Size::ZERO;
+        for digit in '0'..='9' {
+            let digit_text = Text {
+                text: digit.to_string(),
+                runs: Vec::new(),
+                ..self.text.clone()
+            };
+            let digit_size = measurer.measure(&digit_text, None);
+            widest.width = widest.width.max(digit_size.width);
+            widest.height = widest.height.max(digit_size.height);
+        }
+        self.digit_size.set(Some(widest));
+        let size = if Self::is_numeric(&self.text.text) {
+            Size {
+                width: widest.width * self.text.text.chars().count() as f32,
+                height: widest.height,
+            }
+        } else {
+            measurer.measure(&self.text, None)
+        };
+        known_dimensions.unwrap_or(size)
+    }
+    fn take_layout_changed(&mut self) -> bool {
+        std::mem::take(&mut self.layout_changed)
+    }
+}
+
+impl Default for NumberLabel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NumberLabel {
+    pub fn new() -> Self {
+        NumberLabel {
+            text: Text::default(),
+            color: SimpleColors::FG_NORMAL,
+            digit_size: Cell::new(None),
+            layout_changed: false,
         }
     }
 
+    fn is_numeric(text: &str) -> bool {
+        text.chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | ',' | ':'))
+    }
+
     pub fn text(&self) -> &str {
         &self.text.text
     }
     pub fn set_text(&mut self, string: String) {
+        // Only the number of characters affects the size.
+        if string.chars().count() != self.text.text.chars().count() {
+            self.layout_changed = true;
+        }
         self.text.text = string;
     }
+    pub fn set_value(&mut self, value: i64) {
+        self.set_text(value.to_string());
+    }
     pub fn set_font(&mut self, font: FontId) {
         self.text.font = font;
+        self.layout_changed = true;
     }
     pub fn set_font_size(&mut self, font_size: f32) {
         self.text.font_size = font_size;
+        self.layout_changed = true;
     }
     pub fn set_halign(&mut self, h_align: HorizontalAlign) {
         self.text.h_align = h_align;
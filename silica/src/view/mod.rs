@@ -1,13 +1,60 @@
 pub mod button;
 pub mod checkbox;
+pub mod image;
 pub mod label;
+pub mod radio;
+pub mod scroll_view;
+pub mod segmented;
+pub mod slider;
+pub mod text_field;
 
-use crate::{GuiRenderer, PointerState};
+use std::any::Any;
 
-pub trait View: 'static {
+use taffy::{prelude::*, Point};
+
+use crate::{CursorIcon, GuiRenderer, Key, PointerState, TextMeasurer};
+
+// Any is a supertrait so views and controls stored in a Gui can be downcast back to their concrete type.
+pub trait View: Any {
     fn render(&self, renderer: &mut GuiRenderer);
+    // Content size of a leaf node, used when its style doesn't set a size. Dimensions already known from the
+    // style are passed in and should be kept.
+    fn measure(
+        &self,
+        known_dimensions: Size<Option<f32>>,
+        _available_space: Size<AvailableSpace>,
+        _measurer: &TextMeasurer,
+    ) -> Size<f32> {
+        known_dimensions.unwrap_or(Size::ZERO)
+    }
+    // Returns true (once) when the content changed and the node needs to be measured again.
+    fn take_layout_changed(&mut self) -> bool {
+        false
+    }
 }
 
-pub trait Control: 'static {
+pub trait Control: Any {
     fn handle_pointer(&mut self, state: PointerState);
+    // The pointer moved onto or off of the control. Unlike handle_pointer, these aren't affected by keyboard
+    // navigation or button presses.
+    fn on_enter(&mut self) {}
+    fn on_leave(&mut self) {}
+    // Pointer position relative to the top left of the control's node, sent whenever the pointer moves over it
+    // (including right after on_enter). Controls with several parts use it to tell which part is under the pointer.
+    // After the pointer is pressed on the control, it's also sent when the pointer is outside, until on_release, so
+    // the control can be dragged.
+    fn on_move(&mut self, _position: Point<f32>, _size: Size<f32>) {}
+    // The pointer was released after being pressed on the control, wherever the pointer is now.
+    fn on_release(&mut self) {}
+    fn handle_key(&mut self, _key: Key, _repeat: bool) -> bool {
+        false
+    }
+    // A typed character, while the control is highlighted. Returns whether it was used.
+    fn handle_char(&mut self, _character: char) -> bool {
+        false
+    }
+    // Shown while the pointer is over the control, see Gui::cursor_icon.
+    fn cursor_icon(&self) -> CursorIcon {
+        CursorIcon::Default
+    }
 }
@@ -0,0 +1,191 @@
+use grist::{impl_add_event_listener, Event, Obj};
+use taffy::{Point, Rect};
+
+use crate::{Control, CursorIcon, GuiRenderer, PointerState, SimpleColors, View};
+
+pub struct RadioModel {
+    pub enabled: bool,
+    pub state: PointerState,
+    pub selected: bool,
+}
+
+impl Default for RadioModel {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            state: PointerState::None,
+            selected: false,
+        }
+    }
+}
+
+impl RadioModel {
+    pub fn new(selected: bool) -> Self {
+        RadioModel {
+            selected,
+            ..Default::default()
+        }
+    }
+}
+
+pub trait RadioView: 'static {
+    fn render(&self, renderer: &mut GuiRenderer, model: &RadioModel);
+}
+
+#[derive(Default)]
+pub struct SimpleRadioView {
+    colors: SimpleColors,
+}
+
+impl SimpleRadioView {
+    pub fn new(colors: SimpleColors) -> Self {
+        SimpleRadioView { colors }
+    }
+}
+
+impl RadioView for SimpleRadioView {
+    fn render(&self, renderer: &mut GuiRenderer, model: &RadioModel) {
+        renderer.set_color(self.colors.background(model.enabled, model.state));
+        renderer.draw_rect();
+        renderer.set_color(self.colors.foreground(model.enabled));
+        renderer.draw_border(Rect::length(1.));
+        if model.selected {
+            let size = renderer.size();
+            let center = Point {
+                x: size.width / 2.,
+                y: size.height / 2.,
+            };
+            renderer.draw_circle_at(center, size.width.min(size.height) / 4.);
+        }
+    }
+}
+
+// One option of a RadioGroup. Pressing it selects it, but pressing it again doesn't deselect it.
+pub struct RadioButton {
+    model: RadioModel,
+    view: Box<dyn RadioView>,
+    selected: Event<()>,
+}
+
+impl RadioButton {
+    pub fn new<V: RadioView>(model: RadioModel, view: V) -> Self {
+        RadioButton {
+            model,
+            view: Box::new(view),
+            selected: Event::new(),
+        }
+    }
+
+    pub fn is_selected(&self) -> bool {
+        self.model.selected
+    }
+    // Use RadioGroup::set_selected for options in a group, so the others are deselected.
+    pub fn set_selected(&mut self, selected: bool) {
+        self.model.selected = selected;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.model.enabled
+    }
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.model.enabled = enabled;
+    }
+}
+
+impl_add_event_listener!(
+    RadioButton,
+    selected,
+    (),
+    add_selected_listener,
+    remove_selected_listener
+);
+
+impl View for RadioButton {
+    fn render(&self, renderer: &mut GuiRenderer) {
+        self.view.render(renderer, &self.model);
+    }
+}
+
+impl Control for RadioButton {
+    fn handle_pointer(&mut self, state: PointerState) {
+        if self.model.enabled
+            && !self.model.selected
+            && self.model.state == PointerState::Over
+            && state == PointerState::Press
+        {
+            self.model.selected = true;
+            self.selected.emit(&());
+        }
+        self.model.state = state;
+    }
+    fn cursor_icon(&self) -> CursorIcon {
+        if self.model.enabled {
+            CursorIcon::Pointer
+        } else {
+            CursorIcon::Default
+        }
+    }
+}
+
+// Keeps exactly one of its options selected. The options are added to the Gui like any other control.
+pub struct RadioGroup {
+    options: Vec<Obj<RadioButton>>,
+    selected: usize,
+    changed: Event<usize>,
+}
+
+impl RadioGroup {
+    pub fn new(options: Vec<Obj<RadioButton>>, selected: usize) -> Obj<RadioGroup> {
+        assert!(selected < options.len(), "option out of range");
+        for (index, option) in options.iter().enumerate() {
+            option.get_mut().set_selected(index == selected);
+        }
+        let group = Obj::new(RadioGroup {
+            options: options.clone(),
+            selected,
+            changed: Event::new(),
+        });
+        for (index, option) in options.iter().enumerate() {
+            // Weak, since the group owns the options.
+            let group = group.downgrade();
+            option.get_mut().add_selected_listener(move |&()| {
+                if let Some(group) = group.try_upgrade() {
+                    group.get_mut().option_selected(index);
+                }
+            });
+        }
+        group
+    }
+
+    pub fn options(&self) -> &[Obj<RadioButton>] {
+        &self.options
+    }
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+    // Doesn't emit the changed event.
+    pub fn set_selected(&mut self, index: usize) {
+        assert!(index < self.options.len(), "option out of range");
+        self.options[self.selected].get_mut().set_selected(false);
+        self.options[index].get_mut().set_selected(true);
+        self.selected = index;
+    }
+
+    // The pressed option already selected itself, and is still borrowed while it emits, so only the previous one is
+    // changed here.
+    fn option_selected(&mut self, index: usize) {
+        if self.selected != index {
+            self.options[self.selected].get_mut().set_selected(false);
+            self.selected = index;
+            self.changed.emit(&index);
+        }
+    }
+}
+
+impl_add_event_listener!(
+    RadioGroup,
+    changed,
+    usize,
+    add_changed_listener,
+    remove_changed_listener
+);
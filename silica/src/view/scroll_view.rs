@@ -0,0 +1,58 @@
+use taffy::Point;
+
+// A node whose children are clipped to its rect and moved by the scroll offset. Add it with Gui::add_scroll_view.
+// Unlike views it doesn't draw anything, so put it inside a node with a background view if it needs one.
+pub struct ScrollView {
+    offset: Point<f32>,
+    max_offset: Point<f32>,
+    // Logical pixels scrolled for each unit of mouse wheel movement.
+    wheel_step: f32,
+}
+
+impl Default for ScrollView {
+    fn default() -> Self {
+        Self {
+            offset: Point::ZERO,
+            max_offset: Point::ZERO,
+            wheel_step: 24.,
+        }
+    }
+}
+
+impl ScrollView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn scroll_offset(&self) -> Point<f32> {
+        self.offset
+    }
+    // How far the content can scroll, which is how much bigger it is than the scroll view. Updated by Gui::layout.
+    pub fn max_scroll_offset(&self) -> Point<f32> {
+        self.max_offset
+    }
+    // Clamped so the content never scrolls past its ends.
+    pub fn scroll_to(&mut self, offset: Point<f32>) {
+        self.offset = Point {
+            x: offset.x.clamp(0., self.max_offset.x),
+            y: offset.y.clamp(0., self.max_offset.y),
+        };
+    }
+    pub fn set_wheel_step(&mut self, wheel_step: f32) {
+        self.wheel_step = wheel_step;
+    }
+
+    // Returns whether it moved, so the wheel can go to an outer scroll view once this one reaches its end.
+    pub(crate) fn scroll_wheel(&mut self, x: f32, y: f32) -> bool {
+        let offset = self.offset;
+        self.scroll_to(Point {
+            x: offset.x - x * self.wheel_step,
+            y: offset.y - y * self.wheel_step,
+        });
+        self.offset != offset
+    }
+    pub(crate) fn set_max_scroll_offset(&mut self, max_offset: Point<f32>) {
+        self.max_offset = max_offset;
+        self.scroll_to(self.offset);
+    }
+}
@@ -0,0 +1,220 @@
+use glyph_brush::{HorizontalAlign, VerticalAlign};
+use grist::{impl_add_event_listener, Event};
+use taffy::{Point, Rect, Size};
+
+use crate::{Control, CursorIcon, GuiRenderer, Key, PointerState, SimpleColors, Text, View};
+
+pub struct SegmentedModel {
+    pub enabled: bool,
+    pub state: PointerState,
+    // The segment under the pointer, if any.
+    pub hover: Option<usize>,
+    pub selected: usize,
+    pub segments: Vec<Text>,
+}
+
+impl Default for SegmentedModel {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            state: PointerState::None,
+            hover: None,
+            selected: 0,
+            segments: Vec::new(),
+        }
+    }
+}
+
+impl SegmentedModel {
+    pub fn new(labels: &[&str]) -> Self {
+        SegmentedModel {
+            segments: labels
+                .iter()
+                .map(|label| Text {
+                    text: (*label).to_owned(),
+                    h_align: HorizontalAlign::Center,
+                    v_align: VerticalAlign::Center,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    // Segments all have the same width.
+    pub fn segment_width(&self, size: Size<f32>) -> f32 {
+        size.width / self.segments.len().max(1) as f32
+    }
+    pub fn segment_at(&self, position: Point<f32>, size: Size<f32>) -> Option<usize> {
+        if self.segments.is_empty()
+            || position.x < 0.
+            || position.y < 0.
+            || position.x >= size.width
+            || position.y >= size.height
+        {
+            return None;
+        }
+        let index = (position.x / self.segment_width(size)) as usize;
+        Some(index.min(self.segments.len() - 1))
+    }
+}
+
+pub trait SegmentedView: 'static {
+    fn render(&self, renderer: &mut GuiRenderer, model: &SegmentedModel);
+}
+
+#[derive(Default)]
+pub struct SimpleSegmentedView {
+    colors: SimpleColors,
+}
+
+impl SimpleSegmentedView {
+    pub fn new(colors: SimpleColors) -> Self {
+        SimpleSegmentedView { colors }
+    }
+}
+
+impl SegmentedView for SimpleSegmentedView {
+    fn render(&self, renderer: &mut GuiRenderer, model: &SegmentedModel) {
+        let size = renderer.size();
+        let segment_size = Size {
+            width: model.segment_width(size),
+            height: size.height,
+        };
+        let foreground = self.colors.foreground(model.enabled);
+        for (index, text) in model.segments.iter().enumerate() {
+            let point = Point {
+                x: segment_size.width * index as f32,
+                y: 0.,
+            };
+            let background = if !model.enabled {
+                self.colors.bg_disable
+            } else if index == model.selected {
+                self.colors.bg_select
+            } else if model.hover == Some(index) {
+                self.colors.background(true, model.state)
+            } else {
+                self.colors.bg_normal
+            };
+            renderer.set_color(background);
+            renderer.draw_rect_at(point, segment_size);
+            renderer.set_color(foreground);
+            let segment = Rect {
+                left: point.x,
+                right: point.x + segment_size.width,
+                top: 0.,
+                bottom: size.height,
+            };
+            renderer.draw_text_in(segment, text);
+            // Adjacent segments share the divider between them.
+            if index > 0 {
+                renderer.draw_rect_at(
+                    point,
+                    Size {
+                        width: 1.,
+                        height: size.height,
+                    },
+                );
+            }
+        }
+        renderer.set_color(foreground);
+        renderer.draw_border(Rect::length(1.));
+    }
+}
+
+pub struct SegmentedControl {
+    model: SegmentedModel,
+    view: Box<dyn SegmentedView>,
+    changed: Event<usize>,
+}
+
+impl SegmentedControl {
+    pub fn new<V: SegmentedView>(model: SegmentedModel, view: V) -> Self {
+        SegmentedControl {
+            model,
+            view: Box::new(view),
+            changed: Event::new(),
+        }
+    }
+    pub fn with_labels<V: SegmentedView>(labels: &[&str], view: V) -> Self {
+        Self::new(SegmentedModel::new(labels), view)
+    }
+
+    pub fn selected(&self) -> usize {
+        self.model.selected
+    }
+    // Doesn't emit the changed event.
+    pub fn set_selected(&mut self, index: usize) {
+        assert!(index < self.model.segments.len(), "segment out of range");
+        self.model.selected = index;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.model.enabled
+    }
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.model.enabled = enabled;
+    }
+
+    fn select(&mut self, index: usize) {
+        if self.model.selected != index {
+            self.model.selected = index;
+            self.changed.emit(&index);
+        }
+    }
+}
+
+impl_add_event_listener!(
+    SegmentedControl,
+    changed,
+    usize,
+    add_changed_listener,
+    remove_changed_listener
+);
+
+impl View for SegmentedControl {
+    fn render(&self, renderer: &mut GuiRenderer) {
+        self.view.render(renderer, &self.model);
+    }
+}
+
+impl Control for SegmentedControl {
+    fn handle_pointer(&mut self, state: PointerState) {
+        if self.model.enabled
+            && self.model.state == PointerState::Over
+            && state == PointerState::Press
+        {
+            if let Some(index) = self.model.hover {
+                self.select(index);
+            }
+        }
+        self.model.state = state;
+    }
+    fn on_leave(&mut self) {
+        self.model.hover = None;
+    }
+    fn on_move(&mut self, position: Point<f32>, size: Size<f32>) {
+        self.model.hover = self.model.segment_at(position, size);
+    }
+    // Left and right change the selection. At either end they aren't handled, so navigation moves on to the next
+    // control.
+    fn handle_key(&mut self, key: Key, _repeat: bool) -> bool {
+        if !self.model.enabled {
+            return false;
+        }
+        let selected = self.model.selected;
+        match key {
+            Key::Left if selected > 0 => self.select(selected - 1),
+            Key::Right if selected + 1 < self.model.segments.len() => self.select(selected + 1),
+            _ => return false,
+        }
+        true
+    }
+    fn cursor_icon(&self) -> CursorIcon {
+        if self.model.enabled {
+            CursorIcon::Pointer
+        } else {
+            CursorIcon::Default
+        }
+    }
+}
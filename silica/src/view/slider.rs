@@ -0,0 +1,240 @@
+use grist::{impl_add_event_listener, Event};
+use taffy::{Point, Rect, Size};
+
+use crate::{Control, CursorIcon, GuiRenderer, Key, PointerState, SimpleColors, View};
+
+pub struct SliderModel {
+    pub enabled: bool,
+    pub state: PointerState,
+    pub min: f32,
+    pub max: f32,
+    pub value: f32,
+    // How much the left and right keys change the value.
+    pub step: f32,
+    pub dragging: bool,
+}
+
+impl Default for SliderModel {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            state: PointerState::None,
+            min: 0.,
+            max: 1.,
+            value: 0.,
+            step: 0.1,
+            dragging: false,
+        }
+    }
+}
+
+impl SliderModel {
+    pub fn new(min: f32, max: f32, value: f32) -> Self {
+        SliderModel {
+            min,
+            max,
+            value: value.clamp(min, max),
+            step: (max - min) / 10.,
+            ..Default::default()
+        }
+    }
+
+    // Where the value is between min and max, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        if self.max > self.min {
+            ((self.value - self.min) / (self.max - self.min)).clamp(0., 1.)
+        } else {
+            0.
+        }
+    }
+    // The value for a pointer x position across the slider's width.
+    pub fn value_at(&self, x: f32, width: f32) -> f32 {
+        let fraction = if width > 0. {
+            (x / width).clamp(0., 1.)
+        } else {
+            0.
+        };
+        self.min + fraction * (self.max - self.min)
+    }
+}
+
+pub trait SliderView: 'static {
+    fn render(&self, renderer: &mut GuiRenderer, model: &SliderModel);
+}
+
+#[derive(Default)]
+pub struct SimpleSliderView {
+    colors: SimpleColors,
+}
+
+impl SimpleSliderView {
+    pub fn new(colors: SimpleColors) -> Self {
+        SimpleSliderView { colors }
+    }
+}
+
+impl SliderView for SimpleSliderView {
+    fn render(&self, renderer: &mut GuiRenderer, model: &SliderModel) {
+        let size = renderer.size();
+        let foreground = self.colors.foreground(model.enabled);
+        // A thin track across the middle, filled up to the value.
+        let track_height = (size.height / 4.).max(2.);
+        let track_y = (size.height - track_height) / 2.;
+        renderer.set_color(foreground);
+        renderer.draw_rect_at(
+            Point { x: 0., y: track_y },
+            Size {
+                width: size.width,
+                height: track_height,
+            },
+        );
+        let thumb_width = (size.height / 2.).min(size.width);
+        let thumb_x =
+            (model.fraction() * size.width - thumb_width / 2.).clamp(0., size.width - thumb_width);
+        renderer.set_color(if model.enabled {
+            self.colors.bg_select
+        } else {
+            self.colors.bg_disable
+        });
+        renderer.draw_rect_at(
+            Point { x: 0., y: track_y },
+            Size {
+                width: thumb_x,
+                height: track_height,
+            },
+        );
+        let state = if model.dragging {
+            PointerState::Press
+        } else {
+            model.state
+        };
+        renderer.push_translation();
+        renderer.translate(thumb_x, 0.);
+        renderer.set_size(Size {
+            width: thumb_width,
+            height: size.height,
+        });
+        renderer.set_color(self.colors.background(model.enabled, state));
+        renderer.draw_rect();
+        renderer.set_color(foreground);
+        renderer.draw_border(Rect::length(1.));
+        renderer.set_size(size);
+        renderer.pop_translation();
+    }
+}
+
+pub struct Slider {
+    model: SliderModel,
+    view: Box<dyn SliderView>,
+    pointer: Point<f32>,
+    width: f32,
+    changed: Event<f32>,
+}
+
+impl Slider {
+    pub fn new<V: SliderView>(model: SliderModel, view: V) -> Self {
+        Slider {
+            model,
+            view: Box::new(view),
+            pointer: Point::ZERO,
+            width: 0.,
+            changed: Event::new(),
+        }
+    }
+    pub fn with_range<V: SliderView>(min: f32, max: f32, value: f32, view: V) -> Self {
+        Self::new(SliderModel::new(min, max, value), view)
+    }
+
+    pub fn value(&self) -> f32 {
+        self.model.value
+    }
+    // Clamped to the range. Doesn't emit the changed event.
+    pub fn set_value(&mut self, value: f32) {
+        self.model.value = value.clamp(self.model.min, self.model.max);
+    }
+    pub fn set_range(&mut self, min: f32, max: f32) {
+        self.model.min = min;
+        self.model.max = max;
+        self.set_value(self.model.value);
+    }
+    pub fn set_step(&mut self, step: f32) {
+        self.model.step = step;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.model.enabled
+    }
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.model.enabled = enabled;
+        if !enabled {
+            self.model.dragging = false;
+        }
+    }
+
+    fn change(&mut self, value: f32) {
+        let value = value.clamp(self.model.min, self.model.max);
+        if self.model.value != value {
+            self.model.value = value;
+            self.changed.emit(&value);
+        }
+    }
+}
+
+impl_add_event_listener!(
+    Slider,
+    changed,
+    f32,
+    add_changed_listener,
+    remove_changed_listener
+);
+
+impl View for Slider {
+    fn render(&self, renderer: &mut GuiRenderer) {
+        self.view.render(renderer, &self.model);
+    }
+}
+
+impl Control for Slider {
+    // Pressing anywhere on the slider jumps to that value, and dragging follows the pointer, even past the ends.
+    fn handle_pointer(&mut self, state: PointerState) {
+        if self.model.enabled
+            && self.model.state == PointerState::Over
+            && state == PointerState::Press
+        {
+            self.model.dragging = true;
+            self.change(self.model.value_at(self.pointer.x, self.width));
+        }
+        self.model.state = state;
+    }
+    fn on_move(&mut self, position: Point<f32>, size: Size<f32>) {
+        self.pointer = position;
+        self.width = size.width;
+        if self.model.dragging {
+            self.change(self.model.value_at(position.x, size.width));
+        }
+    }
+    fn on_release(&mut self) {
+        self.model.dragging = false;
+    }
+    // Left and right move by the step. At either end they aren't handled, so navigation moves on to the next
+    // control.
+    fn handle_key(&mut self, key: Key, _repeat: bool) -> bool {
+        if !self.model.enabled {
+            return false;
+        }
+        let value = self.model.value;
+        match key {
+            Key::Left if value > self.model.min => self.change(value - self.model.step),
+            Key::Right if value < self.model.max => self.change(value + self.model.step),
+            _ => return false,
+        }
+        true
+    }
+    fn cursor_icon(&self) -> CursorIcon {
+        if self.model.enabled {
+            CursorIcon::Pointer
+        } else {
+            CursorIcon::Default
+        }
+    }
+}
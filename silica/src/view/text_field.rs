@@ -0,0 +1,234 @@
+use glyph_brush::{HorizontalAlign, VerticalAlign};
+use grist::{impl_add_event_listener, Event};
+use taffy::{Point, Rect, Size};
+
+use crate::{Control, CursorIcon, GuiRenderer, Key, PointerState, SimpleColors, Text, View};
+
+pub struct TextFieldModel {
+    pub enabled: bool,
+    pub state: PointerState,
+    pub text: Text,
+    // Byte index into the text, always on a character boundary.
+    pub cursor: usize,
+}
+
+impl Default for TextFieldModel {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            state: PointerState::None,
+            text: Text {
+                h_align: HorizontalAlign::Left,
+                v_align: VerticalAlign::Center,
+                ..Default::default()
+            },
+            cursor: 0,
+        }
+    }
+}
+
+impl TextFieldModel {
+    pub fn new(text: &str) -> Self {
+        let mut model = TextFieldModel::default();
+        model.text.text = text.to_owned();
+        model.cursor = text.len();
+        model
+    }
+
+    fn previous_boundary(&self) -> Option<usize> {
+        self.text.text[..self.cursor]
+            .char_indices()
+            .next_back()
+            .map(|(index, _)| index)
+    }
+    fn next_boundary(&self) -> Option<usize> {
+        self.text.text[self.cursor..]
+            .chars()
+            .next()
+            .map(|character| self.cursor + character.len_utf8())
+    }
+}
+
+pub trait TextFieldView: 'static {
+    fn render(&self, renderer: &mut GuiRenderer, model: &TextFieldModel);
+}
+
+#[derive(Default)]
+pub struct SimpleTextFieldView {
+    colors: SimpleColors,
+}
+
+impl SimpleTextFieldView {
+    const PADDING: f32 = 4.;
+
+    pub fn new(colors: SimpleColors) -> Self {
+        SimpleTextFieldView { colors }
+    }
+}
+
+impl TextFieldView for SimpleTextFieldView {
+    fn render(&self, renderer: &mut GuiRenderer, model: &TextFieldModel) {
+        let size = renderer.size();
+        renderer.set_color(self.colors.background(model.enabled, model.state));
+        renderer.draw_rect();
+        renderer.set_color(self.colors.foreground(model.enabled));
+        renderer.draw_border(Rect::length(1.));
+        let text_rect = Rect {
+            left: Self::PADDING,
+            right: (size.width - Self::PADDING).max(Self::PADDING),
+            top: 0.,
+            bottom: size.height,
+        };
+        renderer.draw_text_in(text_rect, &model.text);
+        // The caret shows while the field is highlighted, which is when it gets typed characters.
+        if model.enabled && model.state != PointerState::None {
+            let before_cursor = Text {
+                text: model.text.text[..model.cursor].to_owned(),
+                runs: Vec::new(),
+                ..model.text.clone()
+            };
+            let caret_x = renderer
+                .measurer()
+                .map(|measurer| measurer.measure(&before_cursor, None).width)
+                .unwrap_or(0.)
+                .min(text_rect.right - text_rect.left);
+            renderer.draw_rect_at(
+                Point {
+                    x: Self::PADDING + caret_x,
+                    y: Self::PADDING,
+                },
+                Size {
+                    width: 1.,
+                    height: (size.height - 2. * Self::PADDING).max(0.),
+                },
+            );
+        }
+    }
+}
+
+pub struct TextField {
+    model: TextFieldModel,
+    view: Box<dyn TextFieldView>,
+    changed: Event<String>,
+}
+
+impl TextField {
+    pub fn new<V: TextFieldView>(model: TextFieldModel, view: V) -> Self {
+        TextField {
+            model,
+            view: Box::new(view),
+            changed: Event::new(),
+        }
+    }
+    pub fn with_text<V: TextFieldView>(text: &str, view: V) -> Self {
+        Self::new(TextFieldModel::new(text), view)
+    }
+
+    pub fn text(&self) -> &str {
+        &self.model.text.text
+    }
+    // Moves the cursor to the end. Doesn't emit the changed event.
+    pub fn set_text(&mut self, text: String) {
+        self.model.cursor = text.len();
+        self.model.text.text = text;
+    }
+    pub fn cursor(&self) -> usize {
+        self.model.cursor
+    }
+    pub fn set_cursor(&mut self, cursor: usize) {
+        assert!(
+            self.model.text.text.is_char_boundary(cursor),
+            "cursor not on a character boundary"
+        );
+        self.model.cursor = cursor;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.model.enabled
+    }
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.model.enabled = enabled;
+    }
+
+    fn emit_changed(&mut self) {
+        let text = self.model.text.text.clone();
+        self.changed.emit(&text);
+    }
+}
+
+impl_add_event_listener!(
+    TextField,
+    changed,
+    String,
+    add_changed_listener,
+    remove_changed_listener
+);
+
+impl View for TextField {
+    fn render(&self, renderer: &mut GuiRenderer) {
+        self.view.render(renderer, &self.model);
+    }
+}
+
+impl Control for TextField {
+    fn handle_pointer(&mut self, state: PointerState) {
+        self.model.state = state;
+    }
+    // Left and right at either end of the text aren't handled, so navigation moves on to the next control. Up and
+    // down always navigate.
+    fn handle_key(&mut self, key: Key, _repeat: bool) -> bool {
+        if !self.model.enabled {
+            return false;
+        }
+        match key {
+            Key::Left => match self.model.previous_boundary() {
+                Some(index) => self.model.cursor = index,
+                None => return false,
+            },
+            Key::Right => match self.model.next_boundary() {
+                Some(index) => self.model.cursor = index,
+                None => return false,
+            },
+            Key::Home => self.model.cursor = 0,
+            Key::End => self.model.cursor = self.model.text.text.len(),
+            Key::Backspace => {
+                if let Some(index) = self.model.previous_boundary() {
+                    self.model
+                        .text
+                        .text
+                        .replace_range(index..self.model.cursor, "");
+                    self.model.cursor = index;
+                    self.emit_changed();
+                }
+            }
+            Key::Delete => {
+                if let Some(index) = self.model.next_boundary() {
+                    self.model
+                        .text
+                        .text
+                        .replace_range(self.model.cursor..index, "");
+                    self.emit_changed();
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+    // Control characters (including the ones some platforms send for backspace and enter) are ignored.
+    fn handle_char(&mut self, character: char) -> bool {
+        if !self.model.enabled || character.is_control() {
+            return false;
+        }
+        self.model.text.text.insert(self.model.cursor, character);
+        self.model.cursor += character.len_utf8();
+        self.emit_changed();
+        true
+    }
+    fn cursor_icon(&self) -> CursorIcon {
+        if self.model.enabled {
+            CursorIcon::Text
+        } else {
+            CursorIcon::Default
+        }
+    }
+}
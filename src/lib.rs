@@ -1,4 +1,12 @@
-use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    rc::{self, Rc},
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError, Weak},
+};
+
+#[cfg(debug_assertions)]
+use std::sync::Mutex;
 
 type Location = &'static std::panic::Location<'static>;
 
@@ -14,30 +22,139 @@ macro_rules! here {
 }
 
 pub struct Value<T: ?Sized> {
+    // Where the value was last borrowed, for borrow error messages. Only tracked in debug builds, since it takes
+    // another lock on every borrow.
+    #[cfg(debug_assertions)]
     last_used: Mutex<Option<Location>>,
+    // Kept outside the lock so downcasting doesn't have to borrow the value.
+    type_id: TypeId,
     inner: RwLock<T>,
 }
 
-impl<T> Value<T> {
+impl<T: 'static> Value<T> {
     pub const fn new(t: T) -> Self {
         Value {
+            #[cfg(debug_assertions)]
             last_used: Mutex::new(None),
+            type_id: TypeId::of::<T>(),
             inner: RwLock::new(t),
         }
     }
 }
-impl<T: Default> Default for Value<T> {
+impl<T: ?Sized> Value<T> {
+    #[cfg(debug_assertions)]
+    fn set_last_used(&self, location: Location) {
+        *self.last_used.lock().unwrap() = Some(location);
+    }
+    #[cfg(not(debug_assertions))]
+    fn set_last_used(&self, _location: Location) {}
+    #[cfg(debug_assertions)]
+    fn last_used(&self) -> Option<Location> {
+        *self.last_used.lock().unwrap()
+    }
+    #[cfg(not(debug_assertions))]
+    fn last_used(&self) -> Option<Location> {
+        None
+    }
+}
+impl<T: Default + 'static> Default for Value<T> {
     fn default() -> Self {
         Value::new(Default::default())
     }
 }
 
+// A shared value that can't be changed, like a loaded asset. Compared to Obj, there's no lock and no borrow tracking,
+// so reading is free and can't fail, but there's no get_mut.
 pub struct Res<T: ?Sized>(Arc<T>);
+pub struct WeakRes<T: ?Sized>(Weak<T>);
+
+impl<T> Res<T> {
+    pub fn new(value: T) -> Self {
+        Res(Arc::new(value))
+    }
+}
+impl<T: ?Sized> Res<T> {
+    pub fn from_rc(rc: Arc<T>) -> Self {
+        Res(rc)
+    }
+    pub fn rc(&self) -> &Arc<T> {
+        &self.0
+    }
+    pub fn rc_weak(&self) -> Weak<T> {
+        Arc::downgrade(&self.0)
+    }
+    pub fn downgrade(&self) -> WeakRes<T> {
+        WeakRes::new(self.rc_weak())
+    }
+}
+impl<T: ?Sized> std::ops::Deref for Res<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+impl<T: ?Sized> Clone for Res<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+impl<T: ?Sized> std::hash::Hash for Res<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state)
+    }
+}
+impl<T: ?Sized> PartialEq for Res<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+impl<T: ?Sized> Eq for Res<T> {}
+
+impl<T: ?Sized> WeakRes<T> {
+    pub fn new(weak: Weak<T>) -> Self {
+        WeakRes(weak)
+    }
+    pub fn exists(&self) -> bool {
+        Weak::strong_count(&self.0) > 0
+    }
+    pub fn try_upgrade(&self) -> Option<Res<T>> {
+        Weak::upgrade(&self.0).map(Res::from_rc)
+    }
+    pub fn upgrade(&self) -> Res<T> {
+        if let Some(res) = self.try_upgrade() {
+            res
+        } else {
+            panic!(
+                "WeakRes<{}> resource no longer exists",
+                std::any::type_name::<T>()
+            );
+        }
+    }
+    pub fn rc_weak(&self) -> &Weak<T> {
+        &self.0
+    }
+}
+impl<T: ?Sized> Clone for WeakRes<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+impl<T: ?Sized> std::hash::Hash for WeakRes<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        Weak::as_ptr(&self.0).hash(state)
+    }
+}
+impl<T: ?Sized> PartialEq for WeakRes<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Weak::ptr_eq(&self.0, &other.0)
+    }
+}
+impl<T: ?Sized> Eq for WeakRes<T> {}
 
 pub struct Obj<T: ?Sized>(Arc<Value<T>>);
 pub struct WeakObj<T: ?Sized>(Weak<Value<T>>);
 
-impl<T> Obj<T> {
+impl<T: 'static> Obj<T> {
     pub fn new(value: T) -> Self {
         Obj(Arc::new(Value::new(value)))
     }
@@ -57,37 +174,110 @@ impl<T: ?Sized> Obj<T> {
     }
 
     #[track_caller]
-    pub fn get(&self) -> RwLockReadGuard<T> {
-        if let Ok(read_guard) = self.0.inner.try_read() {
-            *self.0.last_used.lock().unwrap() = Some(std::panic::Location::caller());
-            read_guard
-        } else {
-            if let Some(previous_location) = self.0.last_used.lock().unwrap().as_ref() {
-                panic!(
-                    "Obj<{}> already borrowed at {}",
-                    std::any::type_name::<T>(),
-                    previous_location
-                );
-            } else {
-                panic!("Obj<{}> unknown error", std::any::type_name::<T>());
+    pub fn get(&self) -> RwLockReadGuard<'_, T> {
+        match self.try_get() {
+            Ok(read_guard) => read_guard,
+            Err(error) => panic!("{}", error),
+        }
+    }
+    #[track_caller]
+    pub fn get_mut(&self) -> RwLockWriteGuard<'_, T> {
+        match self.try_get_mut() {
+            Ok(write_guard) => write_guard,
+            Err(error) => panic!("{}", error),
+        }
+    }
+    // Like get, but returns an error instead of panicking when the value is already mutably borrowed (or poisoned).
+    #[track_caller]
+    pub fn try_get(&self) -> Result<RwLockReadGuard<'_, T>, BorrowError> {
+        match self.0.inner.try_read() {
+            Ok(read_guard) => {
+                self.0.set_last_used(std::panic::Location::caller());
+                Ok(read_guard)
             }
+            Err(error) => Err(self.borrow_error(matches!(error, TryLockError::Poisoned(_)))),
         }
     }
+    // Like get_mut, but returns an error instead of panicking when the value is already borrowed (or poisoned).
     #[track_caller]
-    pub fn get_mut(&self) -> RwLockWriteGuard<T> {
-        if let Ok(write_guard) = self.0.inner.try_write() {
-            *self.0.last_used.lock().unwrap() = Some(std::panic::Location::caller());
-            write_guard
-        } else {
-            if let Some(previous_location) = self.0.last_used.lock().unwrap().as_ref() {
-                panic!(
-                    "Obj<{}> already borrowed at {}",
-                    std::any::type_name::<T>(),
-                    previous_location
-                );
-            } else {
-                panic!("Obj<{}> unknown error", std::any::type_name::<T>());
+    pub fn try_get_mut(&self) -> Result<RwLockWriteGuard<'_, T>, BorrowError> {
+        match self.0.inner.try_write() {
+            Ok(write_guard) => {
+                self.0.set_last_used(std::panic::Location::caller());
+                Ok(write_guard)
             }
+            Err(error) => Err(self.borrow_error(matches!(error, TryLockError::Poisoned(_)))),
+        }
+    }
+    fn borrow_error(&self, poisoned: bool) -> BorrowError {
+        BorrowError {
+            type_name: std::any::type_name::<T>(),
+            poisoned,
+            previous_location: self.0.last_used(),
+        }
+    }
+
+    // A panic while the value is mutably borrowed poisons it, since the value may be left half-modified. Every get
+    // after that panics, until the poison is cleared by something that knows the value is valid.
+    pub fn is_poisoned(&self) -> bool {
+        self.0.inner.is_poisoned()
+    }
+    pub fn clear_poison(&self) {
+        self.0.inner.clear_poison();
+    }
+}
+#[derive(Debug)]
+pub struct BorrowError {
+    type_name: &'static str,
+    poisoned: bool,
+    previous_location: Option<Location>,
+}
+
+impl BorrowError {
+    // Poisoned values stay unusable until Obj::clear_poison, so there's no point trying again.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned
+    }
+    // Where the value was last borrowed, which is usually the borrow that's still held. Always None in release builds.
+    pub fn previous_location(&self) -> Option<Location> {
+        self.previous_location
+    }
+}
+
+impl std::fmt::Display for BorrowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.poisoned, self.previous_location) {
+            (true, Some(previous_location)) => write!(
+                f,
+                "Obj<{}> poisoned by a panic while borrowed at {} (see clear_poison)",
+                self.type_name, previous_location
+            ),
+            (true, None) => write!(
+                f,
+                "Obj<{}> poisoned by a panic while borrowed (see clear_poison)",
+                self.type_name
+            ),
+            (false, Some(previous_location)) => write!(
+                f,
+                "Obj<{}> already borrowed at {}",
+                self.type_name, previous_location
+            ),
+            (false, None) => write!(f, "Obj<{}> already borrowed", self.type_name),
+        }
+    }
+}
+
+impl std::error::Error for BorrowError {}
+
+// Trait objects can be downcast if the trait has Any as a supertrait, by first upcasting to dyn Any.
+impl Obj<dyn Any> {
+    pub fn downcast<T: Any>(&self) -> Option<Obj<T>> {
+        if self.0.type_id == TypeId::of::<T>() {
+            let rc = Arc::into_raw(self.0.clone()) as *const Value<T>;
+            // SAFETY: The value is a T, so this Arc was created as an Arc<Value<T>>.
+            Some(Obj(unsafe { Arc::from_raw(rc) }))
+        } else {
+            None
         }
     }
 }
@@ -132,6 +322,11 @@ impl<T: ?Sized> WeakObj<T> {
         &self.0
     }
 }
+impl WeakObj<dyn Any> {
+    pub fn downcast<T: Any>(&self) -> Option<Obj<T>> {
+        self.try_upgrade().and_then(|obj| obj.downcast())
+    }
+}
 impl<T: ?Sized> Clone for WeakObj<T> {
     fn clone(&self) -> Self {
         Self(self.0.clone())
@@ -155,40 +350,330 @@ macro_rules! obj_upcast {
         $crate::WeakObj::new($obj.rc_weak() as _)
     };
 }
+#[macro_export]
+macro_rules! res_upcast {
+    ($res:expr) => {
+        $crate::WeakRes::new($res.rc_weak() as _)
+    };
+}
+
+// Identifies a listener for Event::remove_listener.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ListenerId(u64);
+
+type Listener<T> = Box<dyn FnMut(&T) -> bool>;
+
+struct Listeners<T> {
+    // Listeners return false once they should be removed. A listener is None while it's being called.
+    listeners: Vec<(ListenerId, Option<Listener<T>>)>,
+    next_id: u64,
+}
+
+impl<T> Listeners<T> {
+    fn push(&mut self, listener: Listener<T>) -> ListenerId {
+        let id = ListenerId(self.next_id);
+        self.next_id += 1;
+        self.listeners.push((id, Some(listener)));
+        id
+    }
+    fn remove(&mut self, id: ListenerId) -> bool {
+        let len = self.listeners.len();
+        self.listeners.retain(|(listener_id, _)| *listener_id != id);
+        self.listeners.len() != len
+    }
+}
+
+fn plain_listener<T, F>(mut f: F) -> Listener<T>
+where
+    F: FnMut(&T) + 'static,
+{
+    Box::new(move |param| {
+        f(param);
+        true
+    })
+}
+fn weak_listener<T, O, F>(obj: WeakObj<O>, mut f: F) -> Listener<T>
+where
+    O: ?Sized + 'static,
+    F: FnMut(&Obj<O>, &T) + 'static,
+{
+    Box::new(move |param| match obj.try_upgrade() {
+        Some(obj) => {
+            f(&obj, param);
+            true
+        }
+        None => false,
+    })
+}
 
 pub struct Event<T> {
-    listeners: Vec<Box<dyn FnMut(&T)>>,
+    listeners: Rc<RefCell<Listeners<T>>>,
 }
 
 impl<T> Event<T> {
     pub fn new() -> Self {
         Event {
-            listeners: Vec::new(),
+            listeners: Rc::new(RefCell::new(Listeners {
+                listeners: Vec::new(),
+                next_id: 0,
+            })),
         }
     }
-    pub fn add_listener<F>(&mut self, f: F)
+    // For adding and removing listeners without access to the event, including from inside one of its listeners.
+    pub fn handle(&self) -> EventHandle<T> {
+        EventHandle(Rc::downgrade(&self.listeners))
+    }
+    pub fn add_listener<F>(&mut self, f: F) -> ListenerId
     where
         F: FnMut(&T) + 'static,
     {
-        self.listeners.push(Box::new(f));
+        self.listeners.borrow_mut().push(plain_listener(f))
+    }
+    // The listener is removed the first time it returns false, e.g. a one-shot listener that always returns false.
+    pub fn add_listener_until<F>(&mut self, f: F) -> ListenerId
+    where
+        F: FnMut(&T) -> bool + 'static,
+    {
+        self.listeners.borrow_mut().push(Box::new(f))
     }
+    // The listener only holds the object weakly, so it doesn't keep the object alive (or make a cycle if the object
+    // owns the event). Once the object is dropped, the listener is removed the next time the event is emitted.
+    pub fn add_weak_listener<O, F>(&mut self, obj: WeakObj<O>, f: F) -> ListenerId
+    where
+        O: ?Sized + 'static,
+        F: FnMut(&Obj<O>, &T) + 'static,
+    {
+        self.listeners.borrow_mut().push(weak_listener(obj, f))
+    }
+    // Returns false if the listener was already removed.
+    pub fn remove_listener(&mut self, id: ListenerId) -> bool {
+        self.listeners.borrow_mut().remove(id)
+    }
+    // Calls the listeners there were when emit started. Listeners removed by an earlier listener aren't called, and
+    // listeners added during emit are first called by the next one.
     pub fn emit(&mut self, param: &T) {
-        for listener in self.listeners.iter_mut() {
-            listener(param);
+        let ids: Vec<ListenerId> = self
+            .listeners
+            .borrow()
+            .listeners
+            .iter()
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            let listener = self
+                .listeners
+                .borrow_mut()
+                .listeners
+                .iter_mut()
+                .find(|(listener_id, _)| *listener_id == id)
+                .and_then(|(_, listener)| listener.take());
+            if let Some(mut listener) = listener {
+                // Not borrowed while the listener runs, so it can use an EventHandle.
+                let keep = listener(param);
+                let mut listeners = self.listeners.borrow_mut();
+                let index = listeners
+                    .listeners
+                    .iter()
+                    .position(|(listener_id, _)| *listener_id == id);
+                // Gone if the listener removed itself.
+                if let Some(index) = index {
+                    if keep {
+                        listeners.listeners[index].1 = Some(listener);
+                    } else {
+                        listeners.listeners.remove(index);
+                    }
+                }
+            }
         }
     }
 }
 
+impl<T> Default for Event<T> {
+    fn default() -> Self {
+        Event::new()
+    }
+}
+
+// Doesn't keep the event alive. Adding a listener after the event was dropped does nothing.
+pub struct EventHandle<T>(rc::Weak<RefCell<Listeners<T>>>);
+
+impl<T> EventHandle<T> {
+    pub fn add_listener<F>(&self, f: F) -> ListenerId
+    where
+        F: FnMut(&T) + 'static,
+    {
+        self.push(plain_listener(f))
+    }
+    pub fn add_listener_until<F>(&self, f: F) -> ListenerId
+    where
+        F: FnMut(&T) -> bool + 'static,
+    {
+        self.push(Box::new(f))
+    }
+    pub fn add_weak_listener<O, F>(&self, obj: WeakObj<O>, f: F) -> ListenerId
+    where
+        O: ?Sized + 'static,
+        F: FnMut(&Obj<O>, &T) + 'static,
+    {
+        self.push(weak_listener(obj, f))
+    }
+    pub fn remove_listener(&self, id: ListenerId) -> bool {
+        match self.0.upgrade() {
+            Some(listeners) => listeners.borrow_mut().remove(id),
+            None => false,
+        }
+    }
+    fn push(&self, listener: Listener<T>) -> ListenerId {
+        match self.0.upgrade() {
+            Some(listeners) => listeners.borrow_mut().push(listener),
+            None => ListenerId(u64::MAX),
+        }
+    }
+}
+impl<T> Clone for EventHandle<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+// Gives a type with an Event field a method to add listeners to it, and optionally one to remove them.
 #[macro_export]
 macro_rules! impl_add_event_listener {
     ($type:ty, $member:ident, $param:ty, $fn_name:ident) => {
         impl $type {
-            pub fn $fn_name<F>(&mut self, f: F)
+            pub fn $fn_name<F>(&mut self, f: F) -> $crate::ListenerId
             where
                 F: FnMut(&$param) + 'static,
             {
-                self.$member.add_listener(f);
+                self.$member.add_listener(f)
             }
         }
     };
+    ($type:ty, $member:ident, $param:ty, $fn_name:ident, $remove_fn_name:ident) => {
+        $crate::impl_add_event_listener!($type, $member, $param, $fn_name);
+        impl $type {
+            pub fn $remove_fn_name(&mut self, id: $crate::ListenerId) -> bool {
+                self.$member.remove_listener(id)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downcast_round_trip() {
+        let obj = Obj::new(5u32);
+        let any: Obj<dyn Any> = Obj::from_rc(obj.rc().clone() as _);
+        assert!(any.downcast::<String>().is_none());
+        let back = any.downcast::<u32>().unwrap();
+        assert!(back == obj);
+        *back.get_mut() = 6;
+        assert_eq!(*obj.get(), 6);
+
+        let weak: WeakObj<dyn Any> = obj_upcast!(obj);
+        assert!(weak.downcast::<u32>().unwrap() == obj);
+        drop((obj, any, back));
+        assert!(weak.downcast::<u32>().is_none());
+    }
+
+    #[test]
+    fn downcast_while_borrowed() {
+        let obj = Obj::new(5u32);
+        let any: Obj<dyn Any> = Obj::from_rc(obj.rc().clone() as _);
+        let _guard = obj.get_mut();
+        assert!(any.downcast::<u32>().is_some());
+    }
+
+    #[test]
+    fn poison_and_clear() {
+        let obj = Obj::new(Vec::<u32>::new());
+        let panicking = obj.clone();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            panicking.get_mut().push(1);
+            let _guard = panicking.get_mut();
+            panic!("while borrowed");
+        }));
+        assert!(result.is_err());
+        assert!(obj.is_poisoned());
+        assert!(obj.try_get().unwrap_err().is_poisoned());
+        assert!(obj.try_get_mut().unwrap_err().is_poisoned());
+
+        obj.clear_poison();
+        assert!(!obj.is_poisoned());
+        assert_eq!(*obj.get(), [1]);
+    }
+
+    #[test]
+    fn already_borrowed_isnt_poisoned() {
+        let obj = Obj::new(0u32);
+        let _guard = obj.get();
+        let error = obj.try_get_mut().unwrap_err();
+        assert!(!error.is_poisoned());
+        assert!(obj.try_get().is_ok());
+    }
+
+    fn recorder() -> Rc<RefCell<Vec<&'static str>>> {
+        Rc::new(RefCell::new(Vec::new()))
+    }
+
+    #[test]
+    fn listener_added_during_emit() {
+        let mut event = Event::<()>::new();
+        let handle = event.handle();
+        let calls = recorder();
+        let record = calls.clone();
+        event.add_listener_until(move |_| {
+            record.borrow_mut().push("first");
+            let record = record.clone();
+            handle.add_listener(move |_| record.borrow_mut().push("added"));
+            false
+        });
+        event.emit(&());
+        assert_eq!(*calls.borrow(), ["first"]);
+        event.emit(&());
+        assert_eq!(*calls.borrow(), ["first", "added"]);
+    }
+
+    #[test]
+    fn listener_removed_during_emit() {
+        let mut event = Event::<()>::new();
+        let handle = event.handle();
+        let calls = recorder();
+        let record = calls.clone();
+        let second = Rc::new(RefCell::new(None));
+        let to_remove = second.clone();
+        let first_record = record.clone();
+        event.add_listener(move |_| {
+            first_record.borrow_mut().push("first");
+            if let Some(id) = to_remove.borrow_mut().take() {
+                assert!(handle.remove_listener(id));
+            }
+        });
+        *second.borrow_mut() =
+            Some(event.add_listener(move |_| record.borrow_mut().push("second")));
+        event.emit(&());
+        event.emit(&());
+        assert_eq!(*calls.borrow(), ["first", "first"]);
+    }
+
+    #[test]
+    fn listener_removes_itself() {
+        let mut event = Event::<()>::new();
+        let handle = event.handle();
+        let calls = recorder();
+        let record = calls.clone();
+        let id = Rc::new(RefCell::new(None));
+        let own_id = id.clone();
+        *id.borrow_mut() = Some(event.add_listener(move |_| {
+            record.borrow_mut().push("once");
+            assert!(handle.remove_listener(own_id.borrow().unwrap()));
+        }));
+        event.emit(&());
+        event.emit(&());
+        assert_eq!(*calls.borrow(), ["once"]);
+        assert!(!event.remove_listener(id.borrow().unwrap()));
+    }
 }